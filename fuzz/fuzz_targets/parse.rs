@@ -0,0 +1,44 @@
+//! Feeds arbitrary bytes to the parser, one telegram at a time and all at once,
+//! and turns whatever parses into graph data. Nothing may panic.
+//!
+//! `cargo +nightly fuzz run parse examples/crashers` from the repository root, starting from the regression fixtures.
+//! Copy a crash it finds from fuzz/artifacts/parse/ to examples/crashers/ once it is fixed,
+//! `test_no_panic_on_crashers` runs everything in there.
+#![no_main]
+
+use dsmr::{collect_readings, parse_telegrams, vector_current, vector_energy, vector_gas, vector_voltage, vector_water, CounterValidator, Gaps, Interval, OnRegression, Readings, TelegramReader, Window};
+use libfuzzer_sys::fuzz_target;
+
+fn graph_data(readings: &Readings) {
+    let window = Window { interval: Interval::Hour, buckets: Some(2) };
+    let [voltage_p1, voltage_p2, voltage_p3] = readings.voltage();
+    vector_voltage(voltage_p1, voltage_p2, voltage_p3, Gaps::Hold);
+    let [current_p1, current_p2, current_p3] = readings.current();
+    vector_current(current_p1, current_p2, current_p3, Gaps::Skip);
+    vector_energy(readings, 0.1, window);
+    vector_water(readings, 0.1, window);
+    vector_gas(readings, 0.1, Window::default());
+}
+
+fuzz_target!(|bytes: &[u8]| {
+    let input = String::from_utf8_lossy(bytes);
+    match parse_telegrams(&input) {
+        Ok(telegrams) => graph_data(&collect_readings(&telegrams)),
+        Err(e) => {
+            let _ = (e.to_string(), e.snippet(&input));
+        }
+    }
+    let mut reader = TelegramReader::new(bytes);
+    let mut counters = CounterValidator::new(OnRegression::Reset, 0.1);
+    let mut readings = Readings::default();
+    while let Some(telegram) = reader.next() {
+        match telegram.and_then(|mut telegram| counters.check(&mut telegram).map(|_| telegram)) {
+            Ok(telegram) => readings.add(&telegram),
+            Err(e) => {
+                let _ = reader.snippet(&e);
+                break;
+            }
+        }
+    }
+    graph_data(&readings);
+});
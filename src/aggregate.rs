@@ -0,0 +1,425 @@
+//! # Aggregation
+//! Flattens parsed telegrams into the series that are plotted by tudelft-dsmr-output-generator.
+use tudelft_dsmr_output_generator::current_over_time::CurrentData;
+use tudelft_dsmr_output_generator::energy_over_time::EnergyData;
+use tudelft_dsmr_output_generator::water_over_time::WaterData;
+use tudelft_dsmr_output_generator::gas_over_time::GasData;
+use tudelft_dsmr_output_generator::voltage_over_time::VoltageData;
+use std::collections::{BTreeMap, BTreeSet};
+use crate::telegram::{EventLogEntry, Information, Phase, Severity, Telegram};
+
+/// Electricity readings of one moment, folded over every electricity meter that reported them.
+/// Per phase the highest voltage and current are kept, power and energy are summed.
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+pub struct ElectricityTotals {
+    pub phases: [Phase; 3], // 7.1.n, 7.2.n, 7.3.n
+    pub consumed: f64, // 7.4.1
+    pub produced: f64, // 7.4.2
+}
+/// Everything that was measured at one moment, for each kind of meter that was present.
+#[derive(Default, Debug, PartialEq, Clone)]
+pub struct Totals {
+    pub electricity: Option<ElectricityTotals>,
+    pub water: Option<u64>, // 6.1, summed
+    pub gas: Option<f64>, // 5.2, summed
+}
+
+impl ElectricityTotals {
+    pub fn combine(self, other: ElectricityTotals) -> ElectricityTotals {
+        let mut phases = self.phases;
+        for (phase, other_phase) in phases.iter_mut().zip(other.phases) {
+            phase.voltage = phase.voltage.max(other_phase.voltage);
+            phase.current = phase.current.max(other_phase.current);
+            phase.power += other_phase.power;
+        }
+        ElectricityTotals {
+            phases,
+            consumed: self.consumed + other.consumed,
+            produced: self.produced + other.produced,
+        }
+    }
+}
+impl Totals {
+    /// Merges the readings of two sets of meters that were read at the same moment
+    pub fn combine(self, other: Totals) -> Totals {
+        Totals {
+            electricity: combine_with(self.electricity, other.electricity, ElectricityTotals::combine),
+            water: combine_with(self.water, other.water, |a, b| a + b),
+            gas: combine_with(self.gas, other.gas, |a, b| a + b),
+        }
+    }
+}
+fn combine_with<T>(a: Option<T>, b: Option<T>, combine: impl FnOnce(T, T) -> T) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(combine(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// The readings of the meter of `telegram` itself, without its children
+fn own_totals(telegram: &Telegram) -> Totals {
+    match &telegram.information {
+        Information::Electricity { phases, consumed, produced, .. } => Totals {
+            electricity: Some(ElectricityTotals { phases: *phases, consumed: *consumed, produced: *produced }),
+            ..Default::default()
+        },
+        Information::Water { litres, .. } => Totals { water: Some(*litres), ..Default::default() },
+        Information::Gas { m3, .. } => Totals { gas: Some(*m3), ..Default::default() },
+    }
+}
+
+fn add_event_log(telegram: &Telegram, event_log: &mut Vec<EventLogEntry>) {
+    event_log.extend(telegram.event_log.iter().cloned());
+    for child_telegram in &telegram.child_telegrams {
+        add_event_log(child_telegram, event_log);
+    }
+}
+
+/// Tells a fixed-width counter register wrapping back to zero apart from a counter that went down.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Rollover {
+    /// Where the register wraps, `None` if it is not known
+    pub capacity: Option<f64>,
+    /// Largest increase between two readings that is still plausible, as a fraction of the capacity.
+    /// A larger increase across the wrap is taken to be a reset instead.
+    pub threshold: f64,
+}
+
+impl Default for Rollover {
+    fn default() -> Rollover {
+        Rollover { capacity: None, threshold: 0.1 }
+    }
+}
+
+impl Rollover {
+    pub fn new(capacity: Option<f64>, threshold: f64) -> Rollover {
+        Rollover { capacity, threshold }
+    }
+
+    /// Whether going from `previous` down to `current` is the register wrapping around
+    pub fn wraps(&self, previous: f64, current: f64) -> bool {
+        match self.capacity {
+            Some(capacity) if current < previous => {
+                let increase = current - previous + capacity;
+                increase >= 0.0 && increase <= self.threshold * capacity
+            }
+            _ => false,
+        }
+    }
+
+    /// How much was counted from `previous` to `current`, across the wrap if there was one
+    pub fn delta(&self, previous: f64, current: f64) -> f64 {
+        match self.capacity {
+            Some(capacity) if self.wraps(previous, current) => current - previous + capacity,
+            _ => current - previous,
+        }
+    }
+}
+
+/// Length of the buckets that counter deltas are summed into.
+/// Buckets follow the wall clock of the meter: a day runs from midnight to midnight in the time written in the telegrams,
+/// summer or winter time alike.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Interval {
+    /// Every reading is its own bucket, stamped with the time of the reading
+    #[default]
+    Reading,
+    Minute,
+    Hour,
+    Day,
+    Month,
+}
+
+impl Interval {
+    /// Start of the bucket that `time_stamp` falls in, on a wall clock that is `utc_offset` seconds ahead of UTC
+    pub fn start(&self, time_stamp: i64, utc_offset: i64) -> i64 {
+        self.local_start(time_stamp + utc_offset) - utc_offset
+    }
+
+    // The same for a time in seconds since 1970-01-01 00:00 on the wall clock
+    fn local_start(&self, local: i64) -> i64 {
+        match self {
+            Interval::Reading => local,
+            Interval::Minute => local - local.rem_euclid(60),
+            Interval::Hour => local - local.rem_euclid(3600),
+            Interval::Day => local - local.rem_euclid(86400),
+            Interval::Month => {
+                let (year, month, _) = civil_from_days(local.div_euclid(86400));
+                days_from_civil(year, month, 1) * 86400
+            }
+        }
+    }
+}
+
+// Date of the day that is `days` after 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153; // March is 0
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+// Inverse of `civil_from_days`
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Which counter deltas are plotted: summed per `interval`, and only the last `buckets` of those if it is set.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Window {
+    pub interval: Interval,
+    pub buckets: Option<usize>,
+}
+
+/// How much one meter's cumulative counter went up at each of its readings, across a wrap of its register.
+/// The first reading has nothing to compare to and counts as an increase of 0.
+pub fn deltas(values: &[(i64, f64)], rollover: Rollover) -> Series<f64> {
+    let mut previous = None;
+    values.iter().map(|&(time_stamp, value)| {
+        let delta = previous.map_or(0.0, |previous| rollover.delta(previous, value));
+        previous = Some(value);
+        (time_stamp, delta)
+    }).collect()
+}
+
+/// Sums `deltas` per bucket of `window.interval`, oldest bucket first, and keeps the last `window.buckets` of them.
+/// `utc_offset` gives the offset of the wall clock at each time stamp, see `Readings::utc_offset`.
+/// Each bucket is stamped with its start, at the offset of its first delta, see `Interval::start`.
+pub fn sum_buckets(deltas: &[(i64, f64)], window: Window, utc_offset: impl Fn(i64) -> i64) -> Series<f64> {
+    // Buckets are told apart by their start on the wall clock, so a day that summer time starts in stays one bucket
+    let mut buckets: Vec<(i64, i64, f64)> = Vec::new();
+    for &(time_stamp, delta) in deltas {
+        let offset = utc_offset(time_stamp);
+        let local_start = window.interval.local_start(time_stamp + offset);
+        match buckets.last_mut() {
+            Some((last_local_start, _, total)) if *last_local_start == local_start => *total += delta,
+            _ => buckets.push((local_start, local_start - offset, delta)),
+        }
+    }
+    if let Some(count) = window.buckets {
+        buckets.drain(..buckets.len().saturating_sub(count));
+    }
+    buckets.into_iter().map(|(_, start, total)| (start, total)).collect()
+}
+
+/// Values of one kind of reading, paired with the time stamp of the telegram they came from, oldest first.
+/// A time stamp occurs at most once.
+pub type Series<T> = Vec<(i64, T)>;
+
+/// What to do at a time stamp where some of the series that are joined have no reading, see `join`.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Gaps {
+    /// Leave the time stamp out
+    #[default]
+    Skip,
+    /// Use the last earlier reading of the series that has none, leave the time stamp out if there is no earlier reading
+    Hold,
+}
+
+/// Joins series on their time stamps, one entry for every time stamp at which any of them has a reading.
+pub fn join<T: Copy, const N: usize>(series: [&[(i64, T)]; N], gaps: Gaps) -> Series<[T; N]> {
+    let time_stamps: BTreeSet<i64> = series.iter().flat_map(|series| series.iter().map(|(time_stamp, _)| *time_stamp)).collect();
+    let mut next = [0; N];
+    let mut last: [Option<T>; N] = [None; N];
+    let mut joined = Vec::new();
+    for time_stamp in time_stamps {
+        let values: [Option<T>; N] = std::array::from_fn(|i| match series[i].get(next[i]) {
+            Some(&(reading_time_stamp, value)) if reading_time_stamp == time_stamp => {
+                next[i] += 1;
+                Some(value)
+            }
+            _ => None,
+        });
+        let values = match gaps {
+            Gaps::Skip => values,
+            Gaps::Hold => {
+                for (last, value) in last.iter_mut().zip(values) {
+                    *last = value.or(*last);
+                }
+                last
+            }
+        };
+        if values.iter().all(Option::is_some) {
+            joined.push((time_stamp, values.map(Option::unwrap)));
+        }
+    }
+    joined
+}
+
+/// Readings of all telegrams, as used by the `vector_*` functions.
+/// Every telegram in the tree counts at its own `2.1` date, a child telegram with a date of its own gets an entry of its own.
+/// Meters that were read at the same moment are merged as by `Totals::combine`,
+/// except for the cumulative counters, which are kept per meter, see `Readings::counter_deltas`.
+#[derive(Default, Debug, PartialEq)]
+pub struct Readings {
+    moments: BTreeMap<i64, Totals>,
+    // Cumulative counters by the place of their meter in the telegram tree and their field, like `CounterValidator`:
+    // the place of the top-level telegram among those with the same time stamp, the place below it and the field
+    counters: BTreeMap<(usize, Vec<usize>, &'static str), Counter>,
+    time_stamp: Option<i64>, // of the last top-level telegram
+    same_time: usize, // how many top-level telegrams came before the last one at its time stamp
+    // How far the clock of the meters was ahead of UTC at each time stamp, from the season of its 2.1 date
+    utc_offsets: BTreeMap<i64, i64>,
+    pub event_log: Vec<EventLogEntry>, // 3.x.n of every telegram, children included
+}
+
+// The readings of one cumulative counter of one meter
+#[derive(Default, Debug, PartialEq)]
+struct Counter {
+    readings: BTreeMap<i64, f64>,
+    capacity: f64, // of the largest register it was read from, see `Rollover`
+}
+
+impl Readings {
+    /// Adds the readings of one top-level telegram and its children
+    pub fn add(&mut self, telegram: &Telegram) {
+        self.same_time = if self.time_stamp == Some(telegram.time_stamp) { self.same_time + 1 } else { 0 };
+        self.time_stamp = Some(telegram.time_stamp);
+        self.add_meters(telegram, &mut Vec::new());
+        add_event_log(telegram, &mut self.event_log);
+    }
+
+    fn add_meters(&mut self, telegram: &Telegram, path: &mut Vec<usize>) {
+        let previous = self.moments.remove(&telegram.time_stamp).unwrap_or_default();
+        self.moments.insert(telegram.time_stamp, previous.combine(own_totals(telegram)));
+        self.utc_offsets.insert(telegram.time_stamp, telegram.telegram_date.utc_offset());
+        match &telegram.information {
+            Information::Electricity { consumed, produced, consumed_capacity, produced_capacity, .. } => {
+                self.add_counter(path, "7.4.1", telegram.time_stamp, *consumed, *consumed_capacity);
+                self.add_counter(path, "7.4.2", telegram.time_stamp, *produced, *produced_capacity);
+            }
+            Information::Water { litres, capacity } => self.add_counter(path, "6.1", telegram.time_stamp, *litres as f64, *capacity as f64),
+            Information::Gas { m3, capacity, .. } => self.add_counter(path, "5.2", telegram.time_stamp, *m3, *capacity),
+        }
+        for (index, child_telegram) in telegram.child_telegrams.iter().enumerate() {
+            path.push(index + 1);
+            self.add_meters(child_telegram, path);
+            path.pop();
+        }
+    }
+
+    fn add_counter(&mut self, path: &[usize], field: &'static str, time_stamp: i64, value: f64, capacity: f64) {
+        let counter = self.counters.entry((self.same_time, path.to_vec(), field)).or_default();
+        counter.readings.insert(time_stamp, value);
+        counter.capacity = counter.capacity.max(capacity);
+    }
+
+    /// The event log entries that are at least as severe as `min_severity`
+    pub fn events_at_least(&self, min_severity: Severity) -> impl Iterator<Item = &EventLogEntry> {
+        self.event_log.iter().filter(move |entry| entry.severity >= min_severity)
+    }
+
+    /// Every time stamp at which a meter was read (2.1)
+    pub fn time_stamps(&self) -> Vec<i64> {
+        self.moments.keys().copied().collect()
+    }
+
+    /// The time stamps at which `value` has a reading, with that reading
+    pub fn series<T>(&self, value: impl Fn(&Totals) -> Option<T>) -> Series<T> {
+        self.moments.iter()
+            .filter_map(|(time_stamp, totals)| value(totals).map(|value| (*time_stamp, value)))
+            .collect()
+    }
+
+    fn phase_series(&self, value: impl Fn(&Phase) -> f64) -> [Series<f64>; 3] {
+        std::array::from_fn(|i| self.series(|totals| totals.electricity.map(|electricity| value(&electricity.phases[i]))))
+    }
+
+    /// 7.1.1, 7.1.2 and 7.1.3
+    pub fn voltage(&self) -> [Series<f64>; 3] {
+        self.phase_series(|phase| phase.voltage)
+    }
+
+    /// 7.2.1, 7.2.2 and 7.2.3
+    pub fn current(&self) -> [Series<f64>; 3] {
+        self.phase_series(|phase| phase.current)
+    }
+
+    /// 7.3.1, 7.3.2 and 7.3.3
+    pub fn power(&self) -> [Series<f64>; 3] {
+        self.phase_series(|phase| phase.power)
+    }
+
+    /// Seconds the clock of the meters was ahead of UTC at `time_stamp`, see `DsmrDateTime::utc_offset`.
+    /// Between readings the offset of the last earlier reading holds, before the first reading that of winter time.
+    pub fn utc_offset(&self, time_stamp: i64) -> i64 {
+        self.utc_offsets.range(..=time_stamp).next_back().map_or(3600, |(_, offset)| *offset)
+    }
+
+    /// Readings of the cumulative counter `field` (7.4.1, 7.4.2, 6.1 or 5.2), one series for every meter that has it
+    pub fn counter(&self, field: &str) -> Vec<Series<f64>> {
+        self.counters.iter()
+            .filter(|((_, _, counter_field), _)| *counter_field == field)
+            .map(|(_, counter)| counter.readings.iter().map(|(time_stamp, value)| (*time_stamp, *value)).collect())
+            .collect()
+    }
+
+    /// How much the counter `field` (7.4.1, 7.4.2, 6.1 or 5.2) went up at every time stamp, summed over the meters that have it.
+    /// Each reading is compared to the previous reading of the same meter, see `deltas`.
+    /// `rollover_threshold` is the `Rollover::threshold` used with the capacity of each meter's register.
+    pub fn counter_deltas(&self, field: &str, rollover_threshold: f64) -> Series<f64> {
+        let mut total: BTreeMap<i64, f64> = BTreeMap::new();
+        for ((_, _, counter_field), counter) in &self.counters {
+            if *counter_field != field {
+                continue;
+            }
+            let readings: Series<f64> = counter.readings.iter().map(|(time_stamp, value)| (*time_stamp, *value)).collect();
+            for (time_stamp, delta) in deltas(&readings, Rollover::new(Some(counter.capacity), rollover_threshold)) {
+                *total.entry(time_stamp).or_default() += delta;
+            }
+        }
+        total.into_iter().collect()
+    }
+}
+
+pub fn collect_readings(telegrams: &[Telegram]) -> Readings {
+    let mut readings = Readings::default();
+    for telegram in telegrams {
+        readings.add(telegram);
+    }
+    readings
+}
+pub fn vector_voltage(voltage_p1: Series<f64>, voltage_p2: Series<f64>, voltage_p3: Series<f64>, gaps: Gaps) -> Vec<VoltageData> {
+    join([&voltage_p1, &voltage_p2, &voltage_p3], gaps).into_iter()
+        .map(|(timestamp, [phase_1, phase_2, phase_3])| VoltageData { phase_1, phase_2, phase_3, timestamp })
+        .collect()
+}
+pub fn vector_current(current_p1: Series<f64>, current_p2: Series<f64>, current_p3: Series<f64>, gaps: Gaps) -> Vec<CurrentData> {
+    join([&current_p1, &current_p2, &current_p3], gaps).into_iter()
+        .map(|(timestamp, [phase_1, phase_2, phase_3])| CurrentData { phase_1, phase_2, phase_3, timestamp })
+        .collect()
+}
+/// Increase of 7.4.2 and 7.4.1 per bucket, see `Readings::counter_deltas`
+pub fn vector_energy(readings: &Readings, rollover_threshold: f64, window: Window) -> Vec<EnergyData> {
+    let utc_offset = |time_stamp| readings.utc_offset(time_stamp);
+    let produced = sum_buckets(&readings.counter_deltas("7.4.2", rollover_threshold), window, utc_offset);
+    let consumed = sum_buckets(&readings.counter_deltas("7.4.1", rollover_threshold), window, utc_offset);
+    // Every electricity meter has both counters, so both have the same buckets
+    join([&produced, &consumed], Gaps::Skip).into_iter()
+        .map(|(timestamp, [produced, consumed])| EnergyData { produced, consumed, timestamp })
+        .collect()
+}
+/// Increase of 6.1 per bucket, see `Readings::counter_deltas`
+pub fn vector_water(readings: &Readings, rollover_threshold: f64, window: Window) -> Vec<WaterData> {
+    sum_buckets(&readings.counter_deltas("6.1", rollover_threshold), window, |time_stamp| readings.utc_offset(time_stamp)).into_iter()
+        // Counters that went down are rejected or raised by `CounterValidator`, do not panic if they were not checked
+        .map(|(timestamp, delta)| WaterData { water_delta: delta.max(0.0) as u64, timestamp })
+        .collect()
+}
+/// Increase of 5.2 per bucket, see `Readings::counter_deltas`
+pub fn vector_gas(readings: &Readings, rollover_threshold: f64, window: Window) -> Vec<GasData> {
+    sum_buckets(&readings.counter_deltas("5.2", rollover_threshold), window, |time_stamp| readings.utc_offset(time_stamp)).into_iter()
+        .map(|(timestamp, gas_delta)| GasData { gas_delta, timestamp })
+        .collect()
+}
@@ -0,0 +1,141 @@
+//! # Counters
+//! Checks that cumulative counters only go up from one telegram to the next.
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use crate::aggregate::Rollover;
+use crate::error::DsmrError;
+use crate::telegram::{Information, Telegram};
+
+/// What to do when a counter is lower than in the previous telegram of the same meter.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OnRegression {
+    /// Reject the telegram with a `DsmrError::CounterRegression`
+    Reject,
+    /// Assume the meter was replaced or reset, and keep counting from the previous reading
+    Reset,
+}
+
+/// A counter that went down and was treated as a meter replacement or reset.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CounterReset {
+    pub telegram: usize, // 1 for the first top-level telegram that was checked
+    pub meter: String, // "telegram" or "child 1, child 2" for the meter at that place in the tree, see `CounterValidator`
+    pub field: &'static str, // 7.4.1, 7.4.2, 6.1 or 5.2
+    pub from: f64,
+    pub to: f64,
+}
+
+impl Display for CounterReset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f, "{} of {} was reset in telegram {}, it went from {} to {}",
+            self.field, self.meter, self.telegram, self.from, self.to
+        )
+    }
+}
+
+/// Compares the cumulative counters (7.4.1, 7.4.2, 6.1 and 5.2) of consecutive top-level telegrams.
+/// A meter is identified by its place in the telegram tree: the top-level telegram, its first child, and so on.
+/// Top-level telegrams with the same time stamp are different meters, told apart by their order,
+/// so the second one at a time stamp is compared to the second one at the time stamp before.
+/// A register that wraps back to zero is not a regression, see `Rollover`.
+pub struct CounterValidator {
+    on_regression: OnRegression,
+    rollover_threshold: f64,
+    telegrams: usize,
+    time_stamp: Option<i64>, // of the last top-level telegram
+    same_time: usize, // how many top-level telegrams came before the last one at its time stamp
+    // Last reading of every counter, after adding the offset of earlier resets
+    last: BTreeMap<CounterKey, f64>,
+    // What is added to the readings of a counter since it was last reset
+    offsets: BTreeMap<CounterKey, f64>,
+    resets: Vec<CounterReset>,
+}
+
+// The place of a top-level telegram among those with the same time stamp, the place of the meter below it and the field
+type CounterKey = (usize, Vec<usize>, &'static str);
+
+impl CounterValidator {
+    /// `rollover_threshold` is the `Rollover::threshold` used to recognise a register that wrapped
+    pub fn new(on_regression: OnRegression, rollover_threshold: f64) -> CounterValidator {
+        CounterValidator {
+            on_regression,
+            rollover_threshold,
+            telegrams: 0,
+            time_stamp: None,
+            same_time: 0,
+            last: BTreeMap::new(),
+            offsets: BTreeMap::new(),
+            resets: Vec::new(),
+        }
+    }
+
+    /// Every reset so far, oldest first
+    pub fn resets(&self) -> &[CounterReset] {
+        &self.resets
+    }
+
+    /// Checks the next top-level telegram against the ones before it.
+    /// With `OnRegression::Reset` the counters of `telegram` are raised so that they keep counting on from before the reset.
+    pub fn check(&mut self, telegram: &mut Telegram) -> Result<(), DsmrError> {
+        self.telegrams += 1;
+        self.same_time = if self.time_stamp == Some(telegram.time_stamp) { self.same_time + 1 } else { 0 };
+        self.time_stamp = Some(telegram.time_stamp);
+        self.check_meter(telegram, &mut Vec::new())
+    }
+
+    fn check_meter(&mut self, telegram: &mut Telegram, path: &mut Vec<usize>) -> Result<(), DsmrError> {
+        match &mut telegram.information {
+            Information::Electricity { consumed, produced, consumed_capacity, produced_capacity, .. } => {
+                self.check_counter(path, "7.4.1", consumed, *consumed_capacity)?;
+                self.check_counter(path, "7.4.2", produced, *produced_capacity)?;
+            }
+            Information::Water { litres, capacity } => {
+                let mut value = *litres as f64;
+                self.check_counter(path, "6.1", &mut value, *capacity as f64)?;
+                *litres = value as u64;
+            }
+            Information::Gas { m3, capacity, .. } => self.check_counter(path, "5.2", m3, *capacity)?,
+        }
+        for (index, child_telegram) in telegram.child_telegrams.iter_mut().enumerate() {
+            path.push(index + 1);
+            self.check_meter(child_telegram, path)?;
+            path.pop();
+        }
+        Ok(())
+    }
+
+    fn check_counter(&mut self, path: &[usize], field: &'static str, value: &mut f64, capacity: f64) -> Result<(), DsmrError> {
+        let key = (self.same_time, path.to_vec(), field);
+        let mut reading = *value + self.offsets.get(&key).copied().unwrap_or_default();
+        let rollover = Rollover::new(Some(capacity), self.rollover_threshold);
+        if let Some(&previous) = self.last.get(&key) {
+            if reading < previous && !rollover.wraps(previous, reading) {
+                let meter = meter_name(self.same_time, path);
+                match self.on_regression {
+                    OnRegression::Reject => return Err(DsmrError::CounterRegression(format!(
+                        "{field} of {meter} went down from {previous} to {reading} in telegram {}", self.telegrams
+                    ))),
+                    OnRegression::Reset => {
+                        self.resets.push(CounterReset { telegram: self.telegrams, meter, field, from: previous, to: *value });
+                        self.offsets.insert(key.clone(), previous);
+                        reading = *value + previous;
+                    }
+                }
+            }
+        }
+        *value = reading;
+        self.last.insert(key, reading);
+        Ok(())
+    }
+}
+
+fn meter_name(same_time: usize, path: &[usize]) -> String {
+    let children = path.iter().map(|index| format!("child {index}")).collect::<Vec<String>>().join(", ");
+    match (same_time, path) {
+        (0, []) => "telegram".to_string(),
+        (0, _) => children,
+        (n, []) => format!("telegram {} at its time stamp", n + 1),
+        (n, _) => format!("telegram {} at its time stamp, {children}", n + 1),
+    }
+}
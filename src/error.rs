@@ -8,44 +8,180 @@ use std::{error::Error, fmt::Display, io};
 
 use tudelft_dsmr_output_generator::PlotError;
 
+/// Where in the input an error was found
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Span {
+    pub offset: usize, // byte offset of the start of the line
+    pub line: usize, // starting at 1
+    pub column: usize, // in characters, starting at 1
+    pub telegram: usize, // position of the top level telegram in the file, starting at 1
+    pub depth: usize, // 0 for a top level telegram, 1 for its children and so on
+}
+
 #[derive(Debug)]
-pub enum MainError {
+pub enum DsmrError {
     IoError(io::Error),
     PlotError(PlotError),
     VersionError(String),
     ExtensionError(String),
     KeyError(String),
     // FloatError(String),
+    MissingField(String),
+    DuplicateField(String),
+    MixedMeter(String),
+    InformationType(String),
+    Nesting(String),
+    InformationTypeCount(String),
+    TooManyChildren(String),
+    ChildNumbering(String),
+    UnitMismatch(String),
+    MissingUnit(String),
+    NumberFormat(String),
+    MissingGasModel(String),
+    UnknownGasModel(String),
+    InvalidDate(String),
+    HeaderError(String),
+    FieldNotInVersion(String),
+    GasWithoutExtension(String),
+    ChildWithoutExtension(String),
+    UnknownSeverity(String),
+    InvalidHex(usize, String),
+    InvalidUtf8(usize, String),
+    CounterRegression(String),
+    TopLevelGas(String),
+    InvalidInterval(String),
+    Spanned(Span, Box<DsmrError>),
 }
 
 // Define how to print out the error when it occurs based on the type of error it is
-impl Display for MainError {
+impl Display for DsmrError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            MainError::IoError(e) => write!(f, "IO Error Occurred: {e}"), // Follow this example
-            MainError::PlotError(e) => write!(f, "Plot Error Occurred: {e}"),
-            MainError::VersionError(e) => write!(f, "Version Error Occurred: {e}"),
-            MainError::ExtensionError(e) => write!(f, "Extension Error Occurred: {e}"),
-            MainError::KeyError(e) => write!(f, "Key Error Occurred: {e}"),
-            // MainError::FloatError(e) => write!(f, "Float Error Occurred: {e}"),
+            DsmrError::IoError(e) => write!(f, "IO Error Occurred: {e}"), // Follow this example
+            DsmrError::PlotError(e) => write!(f, "Plot Error Occurred: {e}"),
+            DsmrError::VersionError(e) => write!(f, "Version Error Occurred: {e}"),
+            DsmrError::ExtensionError(e) => write!(f, "Extension Error Occurred: {e}"),
+            DsmrError::KeyError(e) => write!(f, "Key Error Occurred: {e}"),
+            // DsmrError::FloatError(e) => write!(f, "Float Error Occurred: {e}"),
+            DsmrError::MissingField(e) => write!(f, "Missing Field Error Occurred: {e}"),
+            DsmrError::DuplicateField(e) => write!(f, "Duplicate Field Error Occurred: {e}"),
+            DsmrError::MixedMeter(e) => write!(f, "Mixed Meter Error Occurred: {e}"),
+            DsmrError::InformationType(e) => write!(f, "Information Type Error Occurred: {e}"),
+            DsmrError::Nesting(e) => write!(f, "Nesting Error Occurred: {e}"),
+            DsmrError::InformationTypeCount(e) => write!(f, "Information Type Count Error Occurred: {e}"),
+            DsmrError::TooManyChildren(e) => write!(f, "Too Many Children Error Occurred: {e}"),
+            DsmrError::ChildNumbering(e) => write!(f, "Child Numbering Error Occurred: {e}"),
+            DsmrError::UnitMismatch(e) => write!(f, "Unit Mismatch Error Occurred: {e}"),
+            DsmrError::MissingUnit(e) => write!(f, "Missing Unit Error Occurred: {e}"),
+            DsmrError::NumberFormat(e) => write!(f, "Number Format Error Occurred: {e}"),
+            DsmrError::MissingGasModel(e) => write!(f, "Missing Gas Model Error Occurred: {e}"),
+            DsmrError::UnknownGasModel(e) => write!(f, "Unknown Gas Model Error Occurred: {e}"),
+            DsmrError::InvalidDate(e) => write!(f, "Invalid Date Error Occurred: {e}"),
+            DsmrError::HeaderError(e) => write!(f, "Header Error Occurred: {e}"),
+            DsmrError::FieldNotInVersion(e) => write!(f, "Field Not In Version Error Occurred: {e}"),
+            DsmrError::GasWithoutExtension(e) => write!(f, "Gas Without Extension Error Occurred: {e}"),
+            DsmrError::ChildWithoutExtension(e) => write!(f, "Child Without Extension Error Occurred: {e}"),
+            DsmrError::UnknownSeverity(e) => write!(f, "Unknown Severity Error Occurred: {e}"),
+            DsmrError::InvalidHex(offset, e) => write!(f, "Invalid Hex Error Occurred at offset {offset}: {e}"),
+            DsmrError::InvalidUtf8(offset, e) => write!(f, "Invalid UTF-8 Error Occurred at offset {offset}: {e}"),
+            DsmrError::CounterRegression(e) => write!(f, "Counter Regression Error Occurred: {e}"),
+            DsmrError::TopLevelGas(e) => write!(f, "Top Level Gas Error Occurred: {e}"),
+            DsmrError::InvalidInterval(e) => write!(f, "Invalid Interval Error Occurred: {e}"),
+            DsmrError::Spanned(span, e) => write!(
+                f, "{e} (line {}, column {}, telegram {}, depth {})", span.line, span.column, span.telegram, span.depth
+            ),
         }
     }
 }
 
-// Mark the fact MainError is an Error
-impl Error for MainError {}
+// Mark the fact DsmrError is an Error
+impl Error for DsmrError {}
+
+impl DsmrError {
+    /// Attaches the location of the offending input, unless the error already has a more precise one
+    pub fn at(self, span: &Span) -> DsmrError {
+        match self {
+            DsmrError::Spanned(..) => self,
+            other => DsmrError::Spanned(span.clone(), Box::new(other)),
+        }
+    }
+
+    /// Name of the variant, looking through `Spanned` to the reason inside it
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DsmrError::IoError(..) => "IoError",
+            DsmrError::PlotError(..) => "PlotError",
+            DsmrError::VersionError(..) => "VersionError",
+            DsmrError::ExtensionError(..) => "ExtensionError",
+            DsmrError::KeyError(..) => "KeyError",
+            DsmrError::MissingField(..) => "MissingField",
+            DsmrError::DuplicateField(..) => "DuplicateField",
+            DsmrError::MixedMeter(..) => "MixedMeter",
+            DsmrError::InformationType(..) => "InformationType",
+            DsmrError::Nesting(..) => "Nesting",
+            DsmrError::InformationTypeCount(..) => "InformationTypeCount",
+            DsmrError::TooManyChildren(..) => "TooManyChildren",
+            DsmrError::ChildNumbering(..) => "ChildNumbering",
+            DsmrError::UnitMismatch(..) => "UnitMismatch",
+            DsmrError::MissingUnit(..) => "MissingUnit",
+            DsmrError::NumberFormat(..) => "NumberFormat",
+            DsmrError::MissingGasModel(..) => "MissingGasModel",
+            DsmrError::UnknownGasModel(..) => "UnknownGasModel",
+            DsmrError::InvalidDate(..) => "InvalidDate",
+            DsmrError::HeaderError(..) => "HeaderError",
+            DsmrError::FieldNotInVersion(..) => "FieldNotInVersion",
+            DsmrError::GasWithoutExtension(..) => "GasWithoutExtension",
+            DsmrError::ChildWithoutExtension(..) => "ChildWithoutExtension",
+            DsmrError::UnknownSeverity(..) => "UnknownSeverity",
+            DsmrError::InvalidHex(..) => "InvalidHex",
+            DsmrError::InvalidUtf8(..) => "InvalidUtf8",
+            DsmrError::CounterRegression(..) => "CounterRegression",
+            DsmrError::TopLevelGas(..) => "TopLevelGas",
+            DsmrError::InvalidInterval(..) => "InvalidInterval",
+            DsmrError::Spanned(_, e) => e.kind(),
+        }
+    }
+
+    /// How far into the payload of its field the error is, for errors that know it
+    pub(crate) fn payload_offset(&self) -> usize {
+        match self {
+            DsmrError::InvalidHex(offset, _) | DsmrError::InvalidUtf8(offset, _) => *offset,
+            _ => 0,
+        }
+    }
+
+    /// Shows the offending line of `input` with a caret under the column of the error, compiler style
+    pub fn snippet(&self, input: &str) -> Option<String> {
+        self.snippet_from(input, 0)
+    }
+
+    /// Like `snippet`, for when only the part of the input starting at byte `start` is at hand
+    pub(crate) fn snippet_from(&self, text: &str, start: usize) -> Option<String> {
+        let DsmrError::Spanned(span, _) = self else {
+            return None;
+        };
+        let line = text.get(span.offset.checked_sub(start)?..)?.lines().next().unwrap_or_default();
+        let number = span.line.to_string();
+        let gutter = " ".repeat(number.len());
+        let caret = " ".repeat(span.column.saturating_sub(1));
+        Some(format!(
+            "{gutter}--> line {}, column {} (telegram {}, depth {})\n{gutter} |\n{number} | {line}\n{gutter} | {caret}^",
+            span.line, span.column, span.telegram, span.depth
+        ))
+    }
+}
 
-// Allow converting io:Error to MainError,
+// Allow converting io:Error to DsmrError,
 // which allows the use of the '?' operator to automatically convert this
-impl From<io::Error> for MainError {
+impl From<io::Error> for DsmrError {
     fn from(value: io::Error) -> Self {
-        MainError::IoError(value)
+        DsmrError::IoError(value)
     }
 }
 
-// Allow seamlessly converting PlotError to MainError
-impl From<PlotError> for MainError {
+// Allow seamlessly converting PlotError to DsmrError
+impl From<PlotError> for DsmrError {
     fn from(value: PlotError) -> Self {
-        MainError::PlotError(value)
+        DsmrError::PlotError(value)
     }
 }
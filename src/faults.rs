@@ -0,0 +1,193 @@
+//! # Faults
+//! Breaks a valid .dsmr input in every way the parser has to reject, to test its rejection paths.
+//! Only the first top-level telegram is broken, the rest of the input stays as it was.
+use derive_more::Display;
+
+/// A way of breaking a valid input.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Display)]
+pub enum Fault {
+    DroppedField,
+    DuplicatedId,
+    MixedMeters,
+    GasAtTopLevel,
+    BadUnit,
+    TruncatedHeader,
+    BadHex,
+    TooManyChildren,
+}
+
+impl Fault {
+    pub const ALL: [Fault; 8] = [
+        Fault::DroppedField, Fault::DuplicatedId, Fault::MixedMeters, Fault::GasAtTopLevel,
+        Fault::BadUnit, Fault::TruncatedHeader, Fault::BadHex, Fault::TooManyChildren,
+    ];
+}
+
+/// An input with one fault in it, and the `DsmrError::kind` that parsing it has to give.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FaultCase {
+    pub fault: Fault,
+    pub description: String,
+    pub input: String,
+    pub expected: &'static str,
+}
+
+/// Every faulty variant of `valid` that can be made, grouped by fault.
+/// A fault that `valid` has nothing for, like bad hex without an event log, gives no variants.
+pub fn fault_corpus(valid: &str) -> Vec<FaultCase> {
+    let Some(input) = Input::new(valid) else {
+        return Vec::new();
+    };
+    Fault::ALL.into_iter().flat_map(|fault| input.cases(fault)).collect()
+}
+
+// The lines of a valid input, with the places of the header and the first top-level telegram
+struct Input<'a> {
+    lines: Vec<&'a str>,
+    header: usize,
+    start: usize, // 1.1.0
+    end: usize, // 1.2.0
+}
+
+const GAS_HEADER: &str = "/v12\\+gr";
+
+fn key(line: &str) -> &str {
+    line.split_once('#').map_or(line, |(key, _)| key).trim()
+}
+
+impl<'a> Input<'a> {
+    fn new(valid: &'a str) -> Option<Input<'a>> {
+        let lines: Vec<&str> = valid.lines().collect();
+        let header = lines.iter().position(|line| !line.trim().is_empty())?;
+        let start = lines.iter().position(|line| key(line) == "1.1.0")?;
+        let end = start + lines[start..].iter().position(|line| key(line) == "1.2.0")?;
+        Some(Input { lines, header, start, end })
+    }
+
+    /// The data lines of the first top-level telegram and its children, with their depth below it
+    fn fields(&self) -> Vec<(usize, usize)> {
+        let mut depth = 0;
+        let mut fields = Vec::new();
+        for index in self.start..=self.end {
+            match key(self.lines[index]) {
+                key if key.starts_with("1.1.") => depth += 1,
+                key if key.starts_with("1.2.") => depth -= 1,
+                "" => {}
+                _ => fields.push((index, depth - 1)),
+            }
+        }
+        fields
+    }
+
+    fn case(&self, fault: Fault, description: String, lines: Vec<String>, expected: &'static str) -> FaultCase {
+        let input = lines.into_iter().map(|line| line + "\n").collect();
+        FaultCase { fault, description, input, expected }
+    }
+
+    fn owned(&self) -> Vec<String> {
+        self.lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    /// The lines with the header swapped for one that allows gas meters and children
+    fn with_gas_header(&self) -> Vec<String> {
+        let mut lines = self.owned();
+        lines[self.header] = GAS_HEADER.to_string();
+        lines
+    }
+
+    fn cases(&self, fault: Fault) -> Vec<FaultCase> {
+        match fault {
+            Fault::DroppedField => self.fields().into_iter()
+                // Without 4.1 or 5.1 other rules are broken first
+                .filter(|(index, _)| !matches!(key(self.lines[*index]), "4.1" | "5.1"))
+                .map(|(index, _)| {
+                    let mut lines = self.owned();
+                    let line = lines.remove(index);
+                    self.case(fault, format!("line {} dropped: {line}", index + 1), lines, "MissingField")
+                })
+                .collect(),
+            Fault::DuplicatedId => self.fields().into_iter().map(|(index, _)| {
+                let mut lines = self.owned();
+                lines.insert(index, self.lines[index].to_string());
+                let expected = if key(self.lines[index]) == "4.1" { "InformationTypeCount" } else { "DuplicateField" };
+                self.case(fault, format!("line {} twice: {}", index + 1, self.lines[index]), lines, expected)
+            }).collect(),
+            Fault::MixedMeters => self.fields().into_iter()
+                .filter_map(|(index, _)| {
+                    let other_meter = match self.lines[index].trim() {
+                        "4.1#(E)" | "4.1#(G)" => "6.1#(0000000001*L)",
+                        "4.1#(W)" => "7.4.1#(0000000001.000*kWh)",
+                        _ => return None,
+                    };
+                    let mut lines = self.owned();
+                    lines.insert(index + 1, other_meter.to_string());
+                    Some(self.case(fault, format!("{other_meter} added after line {}", index + 1), lines, "MixedMeter"))
+                })
+                .collect(),
+            Fault::GasAtTopLevel => {
+                let own_fields: Vec<usize> = self.fields().into_iter()
+                    .filter(|(index, depth)| *depth == 0 && (key(self.lines[*index]) == "4.1" || key(self.lines[*index]).starts_with(['6', '7'])))
+                    .map(|(index, _)| index)
+                    .collect();
+                let mut lines = self.with_gas_header();
+                lines.splice(self.end..self.end, ["4.1#(G)", "5.1#(G4)", "5.2#(00001.000*m3)"].map(String::from));
+                for index in own_fields.into_iter().rev() {
+                    lines.remove(index);
+                }
+                vec![self.case(fault, "first telegram turned into a gas meter".to_string(), lines, "TopLevelGas")]
+            }
+            Fault::BadUnit => self.fields().into_iter()
+                .filter_map(|(index, _)| {
+                    let (value, unit) = self.lines[index].trim_end().strip_suffix(')')?.rsplit_once('*')?;
+                    let other_unit = match unit {
+                        "V" => "A",
+                        "A" => "V",
+                        "kW" => "kWh",
+                        "kWh" => "kW",
+                        "L" => "m3",
+                        "m3" => "L",
+                        _ => "Hz",
+                    };
+                    let mut lines = self.owned();
+                    lines[index] = format!("{value}*{other_unit})");
+                    Some(self.case(fault, format!("line {} in {other_unit}: {}", index + 1, lines[index]), lines, "UnitMismatch"))
+                })
+                .collect(),
+            Fault::TruncatedHeader => {
+                let header = self.lines[self.header].trim_end();
+                let mut lengths: Vec<usize> = (0..=header.find('\\').unwrap_or(header.len())).collect();
+                // Cut right after the + that starts the extensions
+                lengths.extend(header.find('+').map(|plus| plus + 1));
+                lengths.into_iter().filter(|length| header.is_char_boundary(*length)).map(|length| {
+                    let mut lines = self.owned();
+                    lines[self.header] = header[..length].to_string();
+                    self.case(fault, format!("header cut to {:?}", &header[..length]), lines, "HeaderError")
+                }).collect()
+            }
+            Fault::BadHex => self.fields().into_iter()
+                .filter(|(index, _)| key(self.lines[*index]).starts_with("3.2."))
+                .flat_map(|(index, _)| {
+                    let line = self.lines[index].trim_end();
+                    let (id, message) = line.strip_suffix(')').and_then(|line| line.split_once("#(")).unwrap_or((line, ""));
+                    let odd = format!("{id}#({})", &message[..message.len().saturating_sub(1)]);
+                    let not_hex = format!("{id}#(g{})", message.get(1..).unwrap_or_default());
+                    [odd, not_hex].into_iter().map(move |broken| (index, broken))
+                })
+                .map(|(index, broken)| {
+                    let mut lines = self.owned();
+                    lines[index] = broken;
+                    self.case(fault, format!("line {} as {}", index + 1, lines[index]), lines, "InvalidHex")
+                })
+                .collect(),
+            Fault::TooManyChildren => {
+                let date = self.fields().into_iter()
+                    .find(|(index, depth)| *depth == 0 && key(self.lines[*index]) == "2.1")
+                    .map_or("2.1#(23-Jul-05 15:26:41 (S))", |(index, _)| self.lines[index].trim_end());
+                let child = ["1.1.1#(START)", date, "4.1#(W)", "6.1#(0000000001*L)", "1.2.1#(END)"].map(String::from);
+                let mut lines = self.with_gas_header();
+                lines.splice(self.end..self.end, std::iter::repeat_n(child, 4).flatten());
+                vec![self.case(fault, "four water meter children added to the first telegram".to_string(), lines, "TooManyChildren")]
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! # Follow
+//! Reading from a file that is still being written to, like `tail -f`.
+use std::io::{self, Read};
+use std::thread;
+use std::time::Duration;
+
+/// Wraps a reader so that reaching the end of it waits for more data instead of ending the input.
+/// Wrap it in a `BufReader` and hand it to `TelegramReader` to get telegrams as they are appended.
+pub struct Follow<R: Read> {
+    inner: R,
+    interval: Duration,
+}
+
+impl<R: Read> Follow<R> {
+    /// `interval` is how long to wait before checking the end of `inner` again
+    pub fn new(inner: R, interval: Duration) -> Follow<R> {
+        Follow { inner, interval }
+    }
+}
+
+impl<R: Read> Read for Follow<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            match self.inner.read(buf)? {
+                0 => thread::sleep(self.interval),
+                read => return Ok(read),
+            }
+        }
+    }
+}
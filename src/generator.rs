@@ -0,0 +1,230 @@
+//! # Generator
+//! Makes up valid telegram streams, for load testing and demos without a real meter.
+//! The same options and seed always give the same telegrams.
+use std::collections::{BTreeMap, BTreeSet};
+use std::f64::consts::PI;
+use crate::aggregate::{civil_from_days, days_from_civil};
+use crate::error::DsmrError;
+use crate::telegram::{DsmrDateTime, EventLogEntry, Extensions, GasModel, Header, Information, Phase, Season, Severity, Telegram, Versions};
+
+/// The kind of meter a generated telegram reports on.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Meter {
+    Electricity,
+    Water,
+    Gas, // only as a child telegram
+}
+
+const MESSAGES: [&str; 5] = ["Power Failure", "Voltage too low", "Voltage too high", "Tamper detected", "Firmware updated"];
+
+/// What to generate.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GeneratorOptions {
+    /// Number of top-level telegrams
+    pub telegrams: usize,
+    /// Date of the first telegram. Later dates keep its season, daylight saving changes are not followed.
+    pub start: DsmrDateTime,
+    /// Seconds between two telegrams, at least 1
+    pub interval: u32,
+    /// Meter of each top-level telegram, taken in turn: `[Electricity, Water]` alternates between the two
+    pub top_level: Vec<Meter>,
+    /// Meters of the children of every telegram, at most 3
+    pub children: Vec<Meter>,
+    /// How many levels of children there are below a top-level telegram
+    pub depth: usize,
+    /// Average current in A of phase 1, 2 and 3, which follows a daily profile with peaks in the morning and evening
+    pub phase_load: [f64; 3],
+    /// Chance that a telegram gets an event log entry, and each further one after that
+    pub event_rate: f64,
+    pub gas_model: GasModel,
+    pub seed: u64,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> GeneratorOptions {
+        GeneratorOptions {
+            telegrams: 1440,
+            start: DsmrDateTime::new(2023, 7, 5, 0, 0, 0, Season::Summer).expect("a valid date"),
+            interval: 60,
+            top_level: vec![Meter::Electricity],
+            children: vec![Meter::Gas],
+            depth: 1,
+            phase_load: [5.0, 5.0, 5.0],
+            event_rate: 0.01,
+            gas_model: GasModel::G4,
+            seed: 0,
+        }
+    }
+}
+
+/// Iterator over generated top-level telegrams.
+/// Counters only go up and every register keeps its width, so the stream also passes `CounterValidator`.
+pub struct Generator {
+    options: GeneratorOptions,
+    random: SplitMix64,
+    generated: usize,
+    // Last reading of every counter, by the place of its meter in the telegram tree
+    counters: BTreeMap<(Vec<usize>, Meter), [f64; 2]>,
+}
+
+impl Generator {
+    /// Rejects options that would give telegrams the parser does not accept
+    pub fn new(options: GeneratorOptions) -> Result<Generator, DsmrError> {
+        if options.top_level.is_empty() || options.top_level.contains(&Meter::Gas) {
+            return Err(DsmrError::InformationType("Top-level telegrams must be electricity or water meters".to_string()));
+        }
+        if options.children.len() > 3 {
+            return Err(DsmrError::TooManyChildren(format!("{} child telegrams, at most 3 are allowed", options.children.len())));
+        }
+        if options.interval == 0 {
+            return Err(DsmrError::InvalidInterval("Telegrams must be at least 1 second apart".to_string()));
+        }
+        let random = SplitMix64(options.seed);
+        Ok(Generator { options, random, generated: 0, counters: BTreeMap::new() })
+    }
+
+    /// The header the generated telegrams need: gas and recursive extensions when they have gas meters or children
+    pub fn header(&self) -> Header {
+        let mut extensions = BTreeSet::new();
+        if self.options.depth > 0 && !self.options.children.is_empty() {
+            extensions.insert(Extensions::Recursive);
+            if self.options.children.contains(&Meter::Gas) {
+                extensions.insert(Extensions::Gas);
+            }
+        }
+        Header { version: Versions::V12, extensions }
+    }
+
+    fn date(&self, index: usize) -> Result<DsmrDateTime, DsmrError> {
+        let start = &self.options.start;
+        let start_seconds = days_from_civil(i64::from(start.year()), i64::from(start.month()), i64::from(start.day())) * 86400
+            + i64::from(start.hour()) * 3600 + i64::from(start.minute()) * 60 + i64::from(start.second());
+        let seconds = start_seconds + index as i64 * i64::from(self.options.interval);
+        let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+        let time = seconds.rem_euclid(86400);
+        if !(2000..2100).contains(&year) {
+            return Err(DsmrError::InvalidDate(format!("Telegram {} would be dated in {year}, outside of the years a telegram can write", index + 1)));
+        }
+        DsmrDateTime::new(year as u16, month as u8, day as u8, (time / 3600) as u8, (time / 60 % 60) as u8, (time % 60) as u8, start.season())
+    }
+
+    fn telegram(&mut self, meter: Meter, date: DsmrDateTime, path: &mut Vec<usize>, header: &Header) -> Result<Telegram, DsmrError> {
+        let information = self.information(meter, &date, path);
+        let event_log = self.event_log(&date);
+        let mut child_telegrams = Vec::new();
+        if path.len() < self.options.depth {
+            for (index, child) in self.options.children.clone().into_iter().enumerate() {
+                path.push(index + 1);
+                child_telegrams.push(self.telegram(child, date, path, header)?);
+                path.pop();
+            }
+        }
+        Ok(Telegram {
+            telegram_version: header.version.clone(),
+            telegram_extensions: header.extensions.clone(),
+            telegram_date: date,
+            time_stamp: date.timestamp()?,
+            event_log,
+            information,
+            child_telegrams,
+        })
+    }
+
+    fn information(&mut self, meter: Meter, date: &DsmrDateTime, path: &[usize]) -> Information {
+        let hours = f64::from(self.options.interval) / 3600.0;
+        let hour = f64::from(date.hour()) + f64::from(date.minute()) / 60.0;
+        // Low at night, a peak around 8 in the morning and a larger one around 19 in the evening
+        let profile = 0.5 + 0.4 * (-(hour - 8.0).powi(2) / 2.0).exp() + 0.8 * (-(hour - 19.0).powi(2) / 4.0).exp();
+        // Solar panels from 6 to 20
+        let sun = ((hour - 6.0) / 14.0 * PI).sin().max(0.0);
+        let key = (path.to_vec(), meter);
+        if !self.counters.contains_key(&key) {
+            let start = [self.random.range(0.0, 10_000.0), self.random.range(0.0, 1_000.0)];
+            self.counters.insert(key.clone(), start);
+        }
+        let [first, second] = self.counters[&key];
+        match meter {
+            Meter::Electricity => {
+                let phases = self.options.phase_load.map(|load| {
+                    let voltage = round(self.random.range(225.0, 235.0), 1);
+                    let current = round(load * profile * self.random.range(0.9, 1.1), 1);
+                    Phase { voltage, current, power: round(voltage * current / 1000.0, 3) }
+                });
+                let used: f64 = phases.iter().map(|phase| phase.power).sum();
+                let consumed = round(first + used * hours, 3);
+                let produced = round(second + 3.0 * sun * self.random.range(0.5, 1.0) * hours, 3);
+                self.counters.insert(key, [consumed, produced]);
+                Information::Electricity { phases, consumed, produced, consumed_capacity: 1e10, produced_capacity: 1e10 }
+            }
+            Meter::Water => {
+                let litres = (first + self.random.range(0.0, 10.0) * profile * f64::from(self.options.interval) / 60.0).round();
+                self.counters.insert(key, [litres, second]);
+                Information::Water { litres: litres as u64, capacity: 10_000_000_000 }
+            }
+            Meter::Gas => {
+                let model = self.options.gas_model;
+                // Readings are kept to the 3 decimals of the model's unit, as a meter writes them
+                let reading = round(first + self.random.range(0.0, 0.5) * profile * hours, 3);
+                let m3 = model.to_m3(reading);
+                self.counters.insert(key, [reading, second]);
+                Information::Gas { model, m3, capacity: model.to_m3(100_000.0) }
+            }
+        }
+    }
+
+    fn event_log(&mut self, date: &DsmrDateTime) -> Vec<EventLogEntry> {
+        let mut event_log = Vec::new();
+        while event_log.len() < 9 && self.random.unit() < self.options.event_rate {
+            let severity = if self.random.unit() < 0.3 { Severity::High } else { Severity::Low };
+            let message = MESSAGES[self.random.below(MESSAGES.len())].to_string();
+            event_log.push(EventLogEntry { id: event_log.len() as u32 + 1, severity, message, date: *date });
+        }
+        event_log
+    }
+}
+
+impl Iterator for Generator {
+    type Item = Result<Telegram, DsmrError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.generated >= self.options.telegrams {
+            return None;
+        }
+        let index = self.generated;
+        self.generated += 1;
+        let meter = self.options.top_level[index % self.options.top_level.len()];
+        let header = self.header();
+        Some(self.date(index).and_then(|date| self.telegram(meter, date, &mut Vec::new(), &header)))
+    }
+}
+
+fn round(value: f64, decimals: i32) -> f64 {
+    let scale = 10f64.powi(decimals);
+    (value * scale).round() / scale
+}
+
+// Small, seedable random number generator, see https://prng.di.unimi.it/splitmix64.c
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn range(&mut self, low: f64, high: f64) -> f64 {
+        low + (high - low) * self.unit()
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
@@ -0,0 +1,41 @@
+//! # dsmr
+//! Parser for DSMR telegrams (versions 1.0 and 1.2, with the gas and recursive extensions),
+//! together with the aggregation of parsed telegrams into graph data and a serializer that writes them back out.
+//!
+//! ```no_run
+//! let input = std::fs::read_to_string("examples/good/simple_gas.dsmr").unwrap();
+//! let telegrams = dsmr::parse_telegrams(&input).unwrap();
+//! let readings = dsmr::collect_readings(&telegrams);
+//! ```
+
+/// Contains `DsmrError`, and code to convert `PlotError` and `io::Error` into a `DsmrError`
+/// https://docs.rs/tudelft-dsmr-output-generator/0.1.3/tudelft_dsmr_output_generator/index.html
+mod error;
+mod aggregate;
+mod counters;
+mod faults;
+mod follow;
+mod generator;
+mod parser;
+mod reader;
+mod serializer;
+mod telegram;
+#[cfg(test)]
+mod test;
+
+pub use aggregate::{collect_readings, deltas, join, sum_buckets, vector_current, vector_energy, vector_gas, vector_voltage, vector_water, ElectricityTotals, Gaps, Interval, Readings, Rollover, Series, Totals, Window};
+pub use counters::{CounterReset, CounterValidator, OnRegression};
+pub use error::{DsmrError, Span};
+pub use faults::{fault_corpus, Fault, FaultCase};
+pub use follow::Follow;
+pub use generator::{Generator, GeneratorOptions, Meter};
+pub use reader::TelegramReader;
+pub use serializer::{header_line, serialize, serialize_telegram};
+pub use telegram::{DsmrDateTime, EventLogEntry, Extensions, GasModel, Header, Information, Phase, Season, Severity, Telegram, Versions};
+
+/// Parses every telegram in the text of a .dsmr file, header included.
+/// Rejects the whole input if any telegram is invalid.
+/// Use `TelegramReader` to read telegrams one at a time instead.
+pub fn parse_telegrams(input: &str) -> Result<Vec<Telegram>, DsmrError> {
+    parser::parse(input)
+}
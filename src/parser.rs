@@ -0,0 +1,678 @@
+//! # Parser
+//! Turns the text of a .dsmr file into `Telegram`s, line by line.
+use std::collections::BTreeSet;
+use crate::error::{DsmrError, Span};
+use crate::telegram::{DsmrDateTime, EventLogEntry, Extensions, GasModel, Header, Information, Phase, Season, Severity, Telegram, Versions};
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Keys {
+    Start, // 1.1.0
+    Date, // 2.1
+    End, // 1.2.0
+    EventLogSeverity(u32), // 3.1.n
+    EventLogMessage(u32), // 3.2.n
+    EventLogDate(u32), // 3.3.n
+    InformationType, // 4,1
+    GasModel, // 5.1
+    GasConsumption, // 5.2
+    WaterConsumption, // 6.1
+    VoltageP1, // 7.1.1
+    VoltageP2, // 7.1.2
+    VoltageP3, // 7.1.3
+    CurrentP1, // 7.2.1
+    CurrentP2, // 7.2.2
+    CurrentP3, // 7.2.3
+    PowerP1, // 7.3.1
+    PowerP2, // 7.3.2
+    PowerP3, // 7.3.3
+    EnergyConsumption, // 7.4.1
+    EnergyProduction, // 7.4.2
+    LineBreak, // NA
+    ChildTelegram1, // 1.1.1
+    ChildTelegram2, // 1.1.2
+    ChildTelegram3, // 1.1.3
+    EndChildTelegram1, // 1.2.1
+    EndChildTelegram2, // 1.2.2
+    EndChildTelegram3, // 1.2.3
+}
+
+/// Raw values of a single telegram, collected line by line.
+/// Only turned into a `Telegram` once its end line has been seen.
+#[derive(Default, Debug, PartialEq)]
+pub(crate) struct TelegramFields {
+    pub(crate) telegram_start: Vec<String>, // 1.1.n
+    pub(crate) telegram_date: Vec<DsmrDateTime>, // 2.1
+    pub(crate) event_log_severity: Vec<(u32, Severity)>, // 3.1.n
+    pub(crate) event_log_message: Vec<(u32, String)>, // 3.2.n
+    pub(crate) event_log_date: Vec<(u32, DsmrDateTime)>, // 3.3.n
+    pub(crate) information_type: Vec<String>, // 4.1
+    pub(crate) gas_model: Vec<GasModel>, // 5.1
+    pub(crate) gas_consumption: Vec<f64>, // 5.2, as read, in the unit of the gas model
+    pub(crate) gas_capacity: Vec<f64>, // 5.2, where the register wraps, in the unit of the gas model
+    pub(crate) water_consumption: Vec<u64>, // 6.1
+    pub(crate) water_capacity: Vec<u64>, // 6.1, where the register wraps
+    pub(crate) voltage_p1: Vec<f64>, // 7.1.1
+    pub(crate) voltage_p2: Vec<f64>, // 7.1.2
+    pub(crate) voltage_p3: Vec<f64>, // 7.1.3
+    pub(crate) current_p1: Vec<f64>, // 7.2.1
+    pub(crate) current_p2: Vec<f64>, // 7.2.2
+    pub(crate) current_p3: Vec<f64>, // 7.2.3
+    pub(crate) power_p1: Vec<f64>, // 7.3.1
+    pub(crate) power_p2: Vec<f64>, // 7.3.2
+    pub(crate) power_p3: Vec<f64>, // 7.3.3
+    pub(crate) energy_consumption: Vec<f64>, // 7.4.1
+    pub(crate) energy_production: Vec<f64>, // 7.4.2
+    pub(crate) energy_consumption_capacity: Vec<f64>, // 7.4.1, where the register wraps
+    pub(crate) energy_production_capacity: Vec<f64>, // 7.4.2, where the register wraps
+    pub(crate) telegram_end: Vec<String>, // 1.2.n
+    pub(crate) child_telegrams: Vec<TelegramFields>, // 1.1.n ... 1.2.n
+    pub(crate) number: usize, // n of 1.1.n
+    pub(crate) span: Span, // location of the 1.1.n line
+}
+pub(crate) fn telegram_ver(telegram_version: &str) -> Result<Versions, DsmrError> {
+    match telegram_version {
+        "10" => Ok(Versions::V10),
+        "12" => Ok(Versions::V12),
+        other => Err(DsmrError::VersionError(format!("Neither Version 10 or 12: {other}"))), // new
+    }
+}
+pub(crate) fn version_ext(version_extension: char) -> Result<Extensions, DsmrError> {
+    match version_extension {
+        'g' => Ok(Extensions::Gas),
+        'r' => Ok(Extensions::Recursive),
+        other => Err(DsmrError::ExtensionError(format!("Invalid Version Extension: {other}"))),
+    }
+}
+pub(crate) fn severity(severity_code: &str) -> Result<Severity, DsmrError> {
+    match severity_code {
+        "H" => Ok(Severity::High),
+        "L" => Ok(Severity::Low),
+        other => Err(DsmrError::UnknownSeverity(format!("Event log severity is neither H or L: {other}"))),
+    }
+}
+/// Parses the header line `/vXX\`, optionally followed by `+` and the extension letters in any order.
+/// `span` points at the start of the line, errors point at the offending character.
+pub(crate) fn parse_header(line: &str, span: &Span) -> Result<Header, DsmrError> {
+    let at_column = |column: usize| Span { column, ..span.clone() };
+    let line = line.trim_end();
+    let Some(header) = line.strip_prefix("/v") else {
+        return Err(DsmrError::HeaderError(format!("Header does not start with /v: {line}")).at(&at_column(1)));
+    };
+    let Some((telegram_version, version_extension)) = header.split_once('\\') else {
+        return Err(DsmrError::HeaderError(format!("Header does not end the version with \\: {line}")).at(&at_column(3)));
+    };
+    let version = telegram_ver(telegram_version).map_err(|e| e.at(&at_column(3)))?;
+
+    // Column of the first character after the backslash
+    let column = 3 + telegram_version.chars().count() + 1;
+    let letters = match version_extension.strip_prefix('+') {
+        Some("") => return Err(DsmrError::HeaderError("No extensions after +".to_string()).at(&at_column(column))),
+        Some(letters) => letters,
+        None if version_extension.is_empty() => "",
+        None => return Err(DsmrError::HeaderError(format!("Expected + before the extensions: {version_extension}")).at(&at_column(column))),
+    };
+    let mut extensions = BTreeSet::new();
+    for (index, letter) in letters.chars().enumerate() {
+        let letter_column = at_column(column + 1 + index);
+        let extension = version_ext(letter).map_err(|e| e.at(&letter_column))?;
+        if !extensions.insert(extension) {
+            return Err(DsmrError::ExtensionError(format!("Extension {letter} given more than once")).at(&letter_column));
+        }
+    }
+    if version == Versions::V10 && !extensions.is_empty() {
+        return Err(DsmrError::ExtensionError("Extensions are only part of version 12".to_string()).at(&at_column(column)));
+    }
+    Ok(Header { version, extensions })
+}
+/// Rejects fields that the version and extensions declared in the header do not allow.
+pub(crate) fn check_header_allows(key: &Keys, telegram_id: &str, payload: &str, header: &Header) -> Result<(), DsmrError> {
+    let is_gas = matches!(key, Keys::GasModel | Keys::GasConsumption) || (*key == Keys::InformationType && payload == "G");
+    let is_child = matches!(
+        key,
+        Keys::ChildTelegram1 | Keys::ChildTelegram2 | Keys::ChildTelegram3
+            | Keys::EndChildTelegram1 | Keys::EndChildTelegram2 | Keys::EndChildTelegram3
+    );
+    match header.version {
+        Versions::V10 if is_gas || is_child => Err(DsmrError::FieldNotInVersion(format!(
+            "{telegram_id}#({payload}) is not part of version 10"
+        ))),
+        Versions::V12 if is_gas && !header.extensions.contains(&Extensions::Gas) => Err(DsmrError::GasWithoutExtension(format!(
+            "{telegram_id}#({payload}) needs the g extension in the header"
+        ))),
+        Versions::V12 if is_child && !header.extensions.contains(&Extensions::Recursive) => Err(DsmrError::ChildWithoutExtension(format!(
+            "{telegram_id}#({payload}) needs the r extension in the header"
+        ))),
+        _ => Ok(()),
+    }
+}
+pub(crate) fn process_lines(lines: &str) -> (String, String) {
+    let parts: Vec<&str> = lines.split('#').collect();
+    if parts.len() == 2 {
+        let telegram_id = parts[0].to_string();
+        // Only strip the outer parentheses, dates end in one of their own: (23-Jul-05 15:26:41 (S))
+        let payload = parts[1].strip_prefix('(').unwrap_or(parts[1]);
+        let payload = payload.strip_suffix(')').unwrap_or(payload).to_string();
+        (telegram_id, payload)
+    } else {
+        ("LineBreak".to_string(), "".to_string())
+    }
+}
+pub(crate) fn version_key(version_key: &str) -> Result<Keys, DsmrError> {
+    match version_key {
+        "1.1.0" => Ok(Keys::Start),
+        "2.1" => Ok(Keys::Date),
+        "1.2.0" => Ok(Keys::End),
+        key if key.starts_with("3.") => event_log_key(key),
+        "4.1" => Ok(Keys::InformationType),
+        "5.1" => Ok(Keys::GasModel),
+        "5.2" => Ok(Keys::GasConsumption),
+        "6.1" => Ok(Keys::WaterConsumption),
+        "7.1.1" => Ok(Keys::VoltageP1),
+        "7.1.2" => Ok(Keys::VoltageP2),
+        "7.1.3" => Ok(Keys::VoltageP3),
+        "7.2.1" => Ok(Keys::CurrentP1),
+        "7.2.2" => Ok(Keys::CurrentP2),
+        "7.2.3" => Ok(Keys::CurrentP3),
+        "7.3.1" => Ok(Keys::PowerP1),
+        "7.3.2" => Ok(Keys::PowerP2),
+        "7.3.3" => Ok(Keys::PowerP3),
+        "7.4.1" => Ok(Keys::EnergyConsumption),
+        "7.4.2" => Ok(Keys::EnergyProduction),
+        "LineBreak" => Ok(Keys::LineBreak),
+        "1.1.1" => Ok(Keys::ChildTelegram1),
+        "1.1.2" => Ok(Keys::ChildTelegram2),
+        "1.1.3" => Ok(Keys::ChildTelegram3),
+        "1.2.1" => Ok(Keys::EndChildTelegram1),
+        "1.2.2" => Ok(Keys::EndChildTelegram2),
+        "1.2.3" => Ok(Keys::EndChildTelegram3),
+        other => Err(DsmrError::KeyError(format!("Invalid Key: {other}"))),
+        // _ => Err("Authentication Failed"),
+    }
+}
+/// 3.1.n, 3.2.n and 3.3.n are the severity, message and date of the event log entry with id n.
+pub(crate) fn event_log_key(key: &str) -> Result<Keys, DsmrError> {
+    let invalid = || DsmrError::KeyError(format!("Invalid Key: {key}"));
+    let (part, id) = key[2..].split_once('.').ok_or_else(invalid)?;
+    let id = id.parse::<u32>().map_err(|_| invalid())?;
+    match part {
+        "1" => Ok(Keys::EventLogSeverity(id)),
+        "2" => Ok(Keys::EventLogMessage(id)),
+        "3" => Ok(Keys::EventLogDate(id)),
+        _ => Err(invalid()),
+    }
+}
+pub(crate) fn gas_model(payload: &str) -> Result<GasModel, DsmrError> {
+    GasModel::ALL.into_iter()
+        .find(|model| model.to_string() == payload)
+        .ok_or_else(|| DsmrError::UnknownGasModel(format!("Unknown gas model {payload}")))
+}
+pub(crate) fn handle_gas_model(payload: &str, telegram: &mut TelegramFields) -> Result<(), DsmrError> {
+    telegram.gas_model.push(gas_model(payload)?);
+    Ok(())
+}
+/// Keeps the 5.2 reading as it is, it is converted by the model of its telegram once the whole telegram has been read.
+pub(crate) fn handle_gas_consumption(payload: &str, telegram: &mut TelegramFields) -> Result<(), DsmrError> {
+    telegram.gas_consumption.push(parse_value::<f64>(payload, "m3")?);
+    telegram.gas_capacity.push(register_capacity(payload, FLOAT_REGISTER_DIGITS)?);
+    Ok(())
+}
+/// Widest register kept as an `f64`, whose capacity has to be finite for `Rollover` to work
+pub(crate) const FLOAT_REGISTER_DIGITS: usize = f64::MAX_10_EXP as usize;
+/// Widest register kept as a `u64`, the capacity of a wider one would not fit
+pub(crate) const INTEGER_REGISTER_DIGITS: usize = u64::MAX.ilog10() as usize;
+/// Where a fixed-width register wraps back to zero: 10 to the power of the number of digits in front of its decimal point.
+/// Registers with more than `max_digits` digits are rejected, their capacity would not fit.
+pub(crate) fn register_capacity(payload: &str, max_digits: usize) -> Result<f64, DsmrError> {
+    let digits = payload.trim_start_matches(['+', '-']).chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > max_digits {
+        return Err(DsmrError::NumberFormat(format!("Register of {digits} digits is wider than {max_digits} in payload: {payload}")));
+    }
+    Ok(10f64.powi(digits as i32))
+}
+/// Units that may be written instead of the unit a field is kept in, and what to multiply by to convert them
+const SMALLER_UNITS: [(&str, &str, f64); 2] = [("kW", "W", 0.001), ("kWh", "Wh", 0.001)];
+/// Splits a `value*unit` payload into its number and its unit.
+pub(crate) fn split_unit(payload: &str) -> Result<(&str, &str), DsmrError> {
+    match payload.split_once('*') {
+        Some((value, unit)) if !unit.is_empty() => Ok((value, unit)),
+        _ => Err(DsmrError::MissingUnit(format!("No unit in payload: {payload}"))),
+    }
+}
+/// What a value in `unit` has to be multiplied by to be in `expected`, which is 1 unless it is a smaller unit.
+pub(crate) fn unit_scale(unit: &str, expected: &str, payload: &str) -> Result<f64, DsmrError> {
+    if unit == expected {
+        return Ok(1.0);
+    }
+    SMALLER_UNITS.iter()
+        .find(|(larger, smaller, _)| *larger == expected && *smaller == unit)
+        .map(|(_, _, scale)| *scale)
+        .ok_or_else(|| DsmrError::UnitMismatch(format!("Expected {expected}, found {unit} in payload: {payload}")))
+}
+/// Whether `value` is written as plain digits with an optional decimal point, like `0012345678.`, `230.1` or `.5`,
+/// preceded by a sign only when `signed`. `FromStr` would also take `NaN`, `inf` and `1e3`.
+fn plain_decimal(value: &str, signed: bool) -> bool {
+    let unsigned = match value.strip_prefix(['+', '-']) {
+        Some(rest) if signed => rest,
+        Some(_) => return false,
+        None => value,
+    };
+    let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    whole.len() + fraction.len() > 0 && whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+}
+fn parse_number<T: std::str::FromStr>(value: &str, payload: &str, signed: bool) -> Result<T, DsmrError> {
+    if !signed && value.starts_with(['+', '-']) {
+        return Err(DsmrError::NumberFormat(format!("Sign on an unsigned reading in payload: {payload}")));
+    }
+    if !plain_decimal(value, signed) {
+        return Err(DsmrError::NumberFormat(format!("Failed to parse number in payload: {payload}")));
+    }
+    value.parse::<T>().map_err(|_| DsmrError::NumberFormat(format!("Failed to parse number in payload: {payload}")))
+}
+/// Parses a payload that has to be in exactly `unit`, like `6.1#(0000000012*L)`. These are counters, which can't be negative.
+pub(crate) fn parse_value<T: std::str::FromStr>(payload: &str, unit: &str) -> Result<T, DsmrError> {
+    let (value, found) = split_unit(payload)?;
+    if found != unit {
+        return Err(DsmrError::UnitMismatch(format!("Expected {unit}, found {found} in payload: {payload}")));
+    }
+    parse_number(value, payload, false)
+}
+/// Parses a payload in `unit` or a smaller unit of it, converted to `unit`: `1500*W` is 1.5 where kW is expected.
+pub(crate) fn parse_quantity(payload: &str, unit: &str) -> Result<f64, DsmrError> {
+    quantity(payload, unit, false)
+}
+/// Like `parse_quantity`, for power which is negative while the meter delivers back, like `-0.5*kW`.
+pub(crate) fn parse_signed_quantity(payload: &str, unit: &str) -> Result<f64, DsmrError> {
+    quantity(payload, unit, true)
+}
+fn quantity(payload: &str, unit: &str, signed: bool) -> Result<f64, DsmrError> {
+    let (value, found) = split_unit(payload)?;
+    let scale = unit_scale(found, unit, payload)?;
+    Ok(parse_number::<f64>(value, payload, signed)? * scale)
+}
+pub(crate) fn handle_water(payload: &str, telegram: &mut TelegramFields) -> Result<(), DsmrError> {
+    telegram.water_consumption.push(parse_value::<u64>(payload, "L")?);
+    telegram.water_capacity.push(register_capacity(payload, INTEGER_REGISTER_DIGITS)? as u64);
+    Ok(())
+}
+pub(crate) fn handle_voltage(payload: &str, telegram: &mut TelegramFields, key: &str) -> Result<(), DsmrError> {
+    let parsed_value = parse_quantity(payload, "V")?;
+    match key {
+        "voltage_p1" => telegram.voltage_p1.push(parsed_value),
+        "voltage_p2" => telegram.voltage_p2.push(parsed_value),
+        "voltage_p3" => telegram.voltage_p3.push(parsed_value),
+        _ => return Err(DsmrError::KeyError(format!("Unexpected key for voltage payload: {key}"))),
+    }
+    Ok(())
+}
+pub(crate) fn handle_current(payload: &str, telegram: &mut TelegramFields, key: &str) -> Result<(), DsmrError> {
+    let parsed_value = parse_quantity(payload, "A")?;
+    match key {
+        "current_p1" => telegram.current_p1.push(parsed_value),
+        "current_p2" => telegram.current_p2.push(parsed_value),
+        "current_p3" => telegram.current_p3.push(parsed_value),
+        _ => return Err(DsmrError::KeyError(format!("Unexpected key for current payload: {key}"))),
+    }
+    Ok(())
+}
+pub(crate) fn handle_power(payload: &str, telegram: &mut TelegramFields, key: &str) -> Result<(), DsmrError> {
+    let parsed_value = parse_signed_quantity(payload, "kW")?;
+    match key {
+        "power_p1" => telegram.power_p1.push(parsed_value),
+        "power_p2" => telegram.power_p2.push(parsed_value),
+        "power_p3" => telegram.power_p3.push(parsed_value),
+        _ => return Err(DsmrError::KeyError(format!("Unexpected key for power payload: {key}"))),
+    }
+    Ok(())
+}
+pub(crate) fn handle_energy(payload: &str, telegram: &mut TelegramFields, key: &str) -> Result<(), DsmrError> {
+    let parsed_value = parse_quantity(payload, "kWh")?;
+    // A register written in Wh wraps at the same number of Wh
+    let capacity = register_capacity(payload, FLOAT_REGISTER_DIGITS)? * unit_scale(split_unit(payload)?.1, "kWh", payload)?;
+    match key {
+        "energy_consumption" => {
+            telegram.energy_consumption.push(parsed_value);
+            telegram.energy_consumption_capacity.push(capacity);
+        }
+        "energy_production" => {
+            telegram.energy_production.push(parsed_value);
+            telegram.energy_production_capacity.push(capacity);
+        }
+        _ => return Err(DsmrError::KeyError(format!("Unexpected key for energy payload: {key}"))),
+    }
+    Ok(())
+}
+/// Decodes a 3.2.n message: pairs of hex digits are bytes, which together must be valid UTF-8.
+/// Errors carry the offset of the offending hex digit within the payload.
+pub(crate) fn hex_string(hex_str: &str) -> Result<String, DsmrError> {
+    let digits: Vec<char> = hex_str.chars().collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(DsmrError::InvalidHex(digits.len() - 1, format!("Odd number of hex digits in {hex_str}")));
+    }
+    let mut bytes = Vec::with_capacity(digits.len() / 2);
+    for (index, pair) in digits.chunks(2).enumerate() {
+        let mut byte = 0;
+        for (position, digit) in pair.iter().enumerate() {
+            let Some(value) = digit.to_digit(16) else {
+                return Err(DsmrError::InvalidHex(2 * index + position, format!("{digit} is not a hex digit in {hex_str}")));
+            };
+            byte = byte * 16 + value as u8;
+        }
+        bytes.push(byte);
+    }
+    String::from_utf8(bytes).map_err(|e| {
+        let offset = 2 * e.utf8_error().valid_up_to();
+        DsmrError::InvalidUtf8(offset, format!("Message {hex_str} is not valid UTF-8 from hex digit {offset} on"))
+    })
+}
+/// Parses a date and time like `23-Jul-05 15:26:41 (S)`: two digit year, month name, day,
+/// time of day and (S) or (W) for summer or winter time.
+pub(crate) fn parse_datetime(input: &str) -> Result<DsmrDateTime, DsmrError> {
+    let invalid = || DsmrError::InvalidDate(format!("Failed to parse date and time: {input}"));
+    let number = |digits: &str| -> Result<u8, DsmrError> {
+        if digits.len() != 2 || !digits.bytes().all(|digit| digit.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        digits.parse::<u8>().map_err(|_| invalid())
+    };
+    let [date, time, season] = input.split_whitespace().collect::<Vec<&str>>()[..] else {
+        return Err(invalid());
+    };
+    let [year, month, day] = date.split('-').collect::<Vec<&str>>()[..] else {
+        return Err(invalid());
+    };
+    let [hour, minute, second] = time.split(':').collect::<Vec<&str>>()[..] else {
+        return Err(invalid());
+    };
+    let month = match month {
+        "Jan" => 1, "Feb" => 2, "Mar" => 3, "Apr" => 4, "May" => 5, "Jun" => 6, "Jul" => 7, "Aug" => 8, "Sep" => 9, "Oct" => 10, "Nov" => 11, "Dec" => 12,
+        _ => return Err(invalid()),
+    };
+    let season = match season {
+        "(S)" => Season::Summer,
+        "(W)" => Season::Winter,
+        _ => return Err(invalid()),
+    };
+    DsmrDateTime::new(2000 + number(year)? as u16, month, number(day)?, number(hour)?, number(minute)?, number(second)?, season)
+}
+/// Returns the only value recorded for `key`, rejecting both a missing and a repeated field.
+pub(crate) fn single<T: Clone>(values: &[T], key: &str) -> Result<T, DsmrError> {
+    match values {
+        [value] => Ok(value.clone()),
+        [] => Err(DsmrError::MissingField(format!("No {key} field in telegram"))),
+        _ => Err(DsmrError::DuplicateField(format!("{} {key} fields in telegram", values.len()))),
+    }
+}
+/// Picks the meter named by the 4.1 information type and rejects fields belonging to any other meter.
+pub(crate) fn build_information(telegram: &TelegramFields) -> Result<Information, DsmrError> {
+    let has_electricity = [
+        &telegram.voltage_p1, &telegram.voltage_p2, &telegram.voltage_p3,
+        &telegram.current_p1, &telegram.current_p2, &telegram.current_p3,
+        &telegram.power_p1, &telegram.power_p2, &telegram.power_p3,
+        &telegram.energy_consumption, &telegram.energy_production,
+    ].iter().any(|values| !values.is_empty());
+    let has_water = !telegram.water_consumption.is_empty();
+    let has_gas = !telegram.gas_model.is_empty() || !telegram.gas_consumption.is_empty();
+
+    match single(&telegram.information_type, "4.1")?.as_str() {
+        "E" if !has_water && !has_gas => {
+            let phase = |voltage: &[f64], current: &[f64], power: &[f64], n: u8| -> Result<Phase, DsmrError> {
+                Ok(Phase {
+                    voltage: single(voltage, &format!("7.1.{n}"))?,
+                    current: single(current, &format!("7.2.{n}"))?,
+                    power: single(power, &format!("7.3.{n}"))?,
+                })
+            };
+            Ok(Information::Electricity {
+                phases: [
+                    phase(&telegram.voltage_p1, &telegram.current_p1, &telegram.power_p1, 1)?,
+                    phase(&telegram.voltage_p2, &telegram.current_p2, &telegram.power_p2, 2)?,
+                    phase(&telegram.voltage_p3, &telegram.current_p3, &telegram.power_p3, 3)?,
+                ],
+                consumed: single(&telegram.energy_consumption, "7.4.1")?,
+                produced: single(&telegram.energy_production, "7.4.2")?,
+                consumed_capacity: single(&telegram.energy_consumption_capacity, "7.4.1")?,
+                produced_capacity: single(&telegram.energy_production_capacity, "7.4.2")?,
+            })
+        }
+        "W" if !has_electricity && !has_gas => Ok(Information::Water {
+            litres: single(&telegram.water_consumption, "6.1")?,
+            capacity: single(&telegram.water_capacity, "6.1")?,
+        }),
+        "G" if !has_electricity && !has_water => {
+            if telegram.gas_model.is_empty() && !telegram.gas_consumption.is_empty() {
+                return Err(DsmrError::MissingGasModel("No 5.1 gas model for the 5.2 gas consumption".to_string()));
+            }
+            let model = single(&telegram.gas_model, "5.1")?;
+            Ok(Information::Gas {
+                model,
+                m3: model.to_m3(single(&telegram.gas_consumption, "5.2")?),
+                capacity: model.to_m3(single(&telegram.gas_capacity, "5.2")?),
+            })
+        }
+        information_type @ ("E" | "W" | "G") => Err(DsmrError::MixedMeter(format!(
+            "Information type {information_type} combined with fields of another meter"
+        ))),
+        other => Err(DsmrError::InformationType(format!("Unknown information type: {other}"))),
+    }
+}
+/// Pairs up the severity, message and date of every event log entry by their id,
+/// rejecting entries that miss one of them or have one twice.
+pub(crate) fn build_event_log(telegram: &TelegramFields) -> Result<Vec<EventLogEntry>, DsmrError> {
+    let ids: BTreeSet<u32> = telegram.event_log_severity.iter().map(|(id, _)| *id)
+        .chain(telegram.event_log_message.iter().map(|(id, _)| *id))
+        .chain(telegram.event_log_date.iter().map(|(id, _)| *id))
+        .collect();
+    ids.into_iter().map(|id| {
+        Ok(EventLogEntry {
+            id,
+            severity: event_log_part(&telegram.event_log_severity, id, 1)?,
+            message: event_log_part(&telegram.event_log_message, id, 2)?,
+            date: event_log_part(&telegram.event_log_date, id, 3)?,
+        })
+    }).collect()
+}
+/// The only 3.`field`.`id` value
+fn event_log_part<T: Clone>(values: &[(u32, T)], id: u32, field: u8) -> Result<T, DsmrError> {
+    let values: Vec<T> = values.iter()
+        .filter(|(value_id, _)| *value_id == id)
+        .map(|(_, value)| value.clone())
+        .collect();
+    single(&values, &format!("3.{field}.{id}"))
+}
+pub(crate) fn build_telegram(telegram: TelegramFields, header: &Header) -> Result<Telegram, DsmrError> {
+    let span = telegram.span.clone();
+    build_telegram_at(telegram, header).map_err(|e| e.at(&span))
+}
+pub(crate) fn build_telegram_at(telegram: TelegramFields, header: &Header) -> Result<Telegram, DsmrError> {
+    single(&telegram.telegram_start, "1.1")?;
+    single(&telegram.telegram_end, "1.2")?;
+    let telegram_date = single(&telegram.telegram_date, "2.1")?;
+    let information = build_information(&telegram)?;
+    let event_log = build_event_log(&telegram)?;
+    let child_telegrams = telegram.child_telegrams.into_iter()
+        .map(|child_telegram| build_telegram(child_telegram, header))
+        .collect::<Result<Vec<Telegram>, DsmrError>>()?;
+    Ok(Telegram {
+        telegram_version: header.version.clone(),
+        telegram_extensions: header.extensions.clone(),
+        time_stamp: telegram_date.timestamp()?,
+        telegram_date,
+        event_log,
+        information,
+        child_telegrams,
+    })
+}
+/// Checks the rules that concern a telegram as a whole, before any of them is turned into a `Telegram`.
+pub(crate) fn validate_telegrams(telegrams: &[TelegramFields]) -> Result<(), DsmrError> {
+    for (index, telegram) in telegrams.iter().enumerate() {
+        validate_telegram(telegram, &format!("telegram {}", index + 1))?;
+    }
+    Ok(())
+}
+/// Checks a top-level telegram and its children. Gas meters can only be children.
+pub(crate) fn validate_telegram(telegram: &TelegramFields, position: &str) -> Result<(), DsmrError> {
+    validate_meter(telegram, position)?;
+    if telegram.information_type.first().map(String::as_str) == Some("G") {
+        return Err(DsmrError::TopLevelGas(format!("{position} is a gas meter, which can only be a child telegram")).at(&telegram.span));
+    }
+    Ok(())
+}
+fn validate_meter(telegram: &TelegramFields, position: &str) -> Result<(), DsmrError> {
+    if telegram.information_type.len() != 1 {
+        return Err(DsmrError::InformationTypeCount(format!(
+            "{position} has {} 4.1 fields instead of exactly one", telegram.information_type.len()
+        )).at(&telegram.span));
+    }
+    if telegram.child_telegrams.len() > 3 {
+        return Err(DsmrError::TooManyChildren(format!(
+            "{position} has {} child telegrams, at most 3 are allowed", telegram.child_telegrams.len()
+        )).at(&telegram.span));
+    }
+    for (index, child_telegram) in telegram.child_telegrams.iter().enumerate() {
+        // Children are numbered 1.1.1, 1.1.2 and 1.1.3 in the order they appear
+        if child_telegram.number != index + 1 {
+            return Err(DsmrError::ChildNumbering(format!(
+                "child {} of {position} is numbered 1.1.{} instead of 1.1.{}", index + 1, child_telegram.number, index + 1
+            )).at(&child_telegram.span));
+        }
+        validate_meter(child_telegram, &format!("{position}, child {}", index + 1))?;
+    }
+    Ok(())
+}
+/// Whether an end line (1.2.n) belongs to the telegram opened by the start line (1.1.n).
+pub(crate) fn closes(start: &Keys, end: &Keys) -> bool {
+    matches!(
+        (start, end),
+        (Keys::Start, Keys::End)
+            | (Keys::ChildTelegram1, Keys::EndChildTelegram1)
+            | (Keys::ChildTelegram2, Keys::EndChildTelegram2)
+            | (Keys::ChildTelegram3, Keys::EndChildTelegram3)
+    )
+}
+/// Records a single data field in the telegram that is currently being read.
+pub(crate) fn handle_field(key: Keys, payload: &str, telegram: &mut TelegramFields) -> Result<(), DsmrError> {
+    match key {
+        Keys::Date => telegram.telegram_date.push(parse_datetime(payload)?),
+        Keys::EventLogSeverity(id) => telegram.event_log_severity.push((id, severity(payload)?)),
+        Keys::EventLogMessage(id) => telegram.event_log_message.push((id, hex_string(payload)?)),
+        Keys::EventLogDate(id) => telegram.event_log_date.push((id, parse_datetime(payload)?)),
+        Keys::InformationType => telegram.information_type.push(payload.to_string()),
+        Keys::GasModel => handle_gas_model(payload, telegram)?,
+        Keys::GasConsumption => handle_gas_consumption(payload, telegram)?,
+        Keys::WaterConsumption => handle_water(payload, telegram)?,
+        Keys::VoltageP1 => handle_voltage(payload, telegram, "voltage_p1")?,
+        Keys::VoltageP2 => handle_voltage(payload, telegram, "voltage_p2")?,
+        Keys::VoltageP3 => handle_voltage(payload, telegram, "voltage_p3")?,
+        Keys::CurrentP1 => handle_current(payload, telegram, "current_p1")?,
+        Keys::CurrentP2 => handle_current(payload, telegram, "current_p2")?,
+        Keys::CurrentP3 => handle_current(payload, telegram, "current_p3")?,
+        Keys::PowerP1 => handle_power(payload, telegram, "power_p1")?,
+        Keys::PowerP2 => handle_power(payload, telegram, "power_p2")?,
+        Keys::PowerP3 => handle_power(payload, telegram, "power_p3")?,
+        Keys::EnergyConsumption => handle_energy(payload, telegram, "energy_consumption")?,
+        Keys::EnergyProduction => handle_energy(payload, telegram, "energy_production")?,
+        Keys::Start | Keys::ChildTelegram1 | Keys::ChildTelegram2 | Keys::ChildTelegram3
+        | Keys::End | Keys::EndChildTelegram1 | Keys::EndChildTelegram2 | Keys::EndChildTelegram3
+        | Keys::LineBreak => {
+            // Framing lines are handled by `parse`
+        }
+    }
+    Ok(())
+}
+/// Handles one line of the input. `span` points at the start of the line.
+pub(crate) fn parse_line(line: &str, span: &Span, header: &Header, telegrams: &mut Vec<TelegramFields>, open_telegrams: &mut Vec<(Keys, TelegramFields)>) -> Result<(), DsmrError> {
+    let (telegram_id, payload) = process_lines(line);
+    let key = version_key(&telegram_id)?;
+    check_header_allows(&key, &telegram_id, &payload, header)?;
+    match key {
+        Keys::LineBreak => {
+            // Do nothing
+        },
+        Keys::Start => {
+            if !open_telegrams.is_empty() {
+                return Err(DsmrError::Nesting(format!("{telegram_id} found before the previous telegram ended")));
+            }
+            let mut telegram = TelegramFields { span: span.clone(), ..Default::default() };
+            telegram.telegram_start.push(payload);
+            open_telegrams.push((Keys::Start, telegram));
+        },
+        key @ (Keys::ChildTelegram1 | Keys::ChildTelegram2 | Keys::ChildTelegram3) => {
+            if open_telegrams.is_empty() {
+                return Err(DsmrError::Nesting(format!("{telegram_id} found outside of a telegram")));
+            }
+            let depth = open_telegrams.len();
+            let number = match key {
+                Keys::ChildTelegram1 => 1,
+                Keys::ChildTelegram2 => 2,
+                _ => 3,
+            };
+            let mut child_telegram = TelegramFields { number, span: Span { depth, ..span.clone() }, ..Default::default() };
+            child_telegram.telegram_start.push(payload);
+            open_telegrams.push((key, child_telegram));
+        },
+        key @ (Keys::End | Keys::EndChildTelegram1 | Keys::EndChildTelegram2 | Keys::EndChildTelegram3) => {
+            let mut telegram = match open_telegrams.pop() {
+                Some((start, telegram)) if closes(&start, &key) => telegram,
+                _ => return Err(DsmrError::Nesting(format!("{telegram_id} does not match the telegram it ends"))),
+            };
+            telegram.telegram_end.push(payload);
+            match open_telegrams.last_mut() {
+                Some((_, parent)) => parent.child_telegrams.push(telegram),
+                None => telegrams.push(telegram),
+            }
+        },
+        key => match open_telegrams.last_mut() {
+            Some((_, telegram)) => {
+                // Point at the payload rather than at the start of the line
+                let column = line.find('#').map_or(1, |index| line[..index].chars().count() + 3);
+                handle_field(key, &payload, telegram).map_err(|e| {
+                    let column = column + e.payload_offset();
+                    e.at(&Span { column, ..span.clone() })
+                })?
+            },
+            None => return Err(DsmrError::Nesting(format!("{telegram_id} found outside of a telegram"))),
+        },
+    };
+    Ok(())
+}
+pub(crate) fn parse(input: &str) -> Result<Vec<Telegram>, DsmrError> {
+    // cd User/Y1S1-SoftwareFundamentals/2023-09-12_Proj-Individual/btee
+    // Note that you can use this function:
+    // tudelft_dsmr_output_generator::date_to_timestamp(year, month, day, hour, minute, seconds, dst)
+    // let l = lines.len(); // print!("{}",l); // print!("{}",input);
+
+    let mut telegrams: Vec<TelegramFields> = Vec::new();
+    // The telegram that is currently being read is last, its parents (if any) come before it
+    let mut open_telegrams: Vec<(Keys, TelegramFields)> = Vec::new();
+
+    let mut header: Option<Header> = None;
+    let mut offset = 0;
+    for (index, raw_line) in input.split_inclusive('\n').enumerate() {
+        let line = raw_line.trim_end_matches('\n').trim_end_matches('\r');
+        let span = Span {
+            offset,
+            line: index + 1,
+            column: 1,
+            telegram: telegrams.len() + 1,
+            depth: open_telegrams.len().saturating_sub(1),
+        };
+        if let Some(header) = &header {
+            parse_line(line, &span, header, &mut telegrams, &mut open_telegrams).map_err(|e| e.at(&span))?;
+        } else if !line.trim().is_empty() {
+            // The first line that is not empty is the header
+            header = Some(parse_header(line, &span)?);
+        }
+        offset += raw_line.len();
+    }
+    let Some(header) = header else {
+        return Err(DsmrError::HeaderError("No header found".to_string()));
+    };
+    if let Some((_, telegram)) = open_telegrams.last() {
+        return Err(DsmrError::MissingField("No 1.2.0 field at the end of the last telegram".to_string()).at(&telegram.span));
+    }
+    validate_telegrams(&telegrams)?;
+    telegrams.into_iter()
+        .map(|telegram| build_telegram(telegram, &header))
+        .collect()
+}
+
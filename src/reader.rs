@@ -0,0 +1,118 @@
+//! # Reader
+//! Reads telegrams one at a time from any `BufRead`, so input of any length is handled in constant memory.
+use std::io::BufRead;
+use crate::error::{DsmrError, Span};
+use crate::parser::{build_telegram, parse_header, parse_line, validate_telegram, Keys, TelegramFields};
+use crate::telegram::{Header, Telegram};
+
+/// Iterator over the top-level telegrams of a .dsmr stream.
+/// A telegram is yielded as soon as its `1.2.0#(END)` line has been read.
+/// After the first error the iterator yields nothing more, like `parse_telegrams` rejecting the whole input.
+pub struct TelegramReader<R: BufRead> {
+    reader: R,
+    header: Option<Header>,
+    // The telegram that is currently being read is last, its parents (if any) come before it
+    open_telegrams: Vec<(Keys, TelegramFields)>,
+    finished_telegrams: Vec<TelegramFields>,
+    // Number of top-level telegrams read so far
+    telegrams: usize,
+    line: usize,
+    offset: usize,
+    // Raw text of the telegram that is currently being read, starting at byte `text_offset` of the input
+    text: String,
+    text_offset: usize,
+    done: bool,
+}
+
+impl<R: BufRead> TelegramReader<R> {
+    pub fn new(reader: R) -> TelegramReader<R> {
+        TelegramReader {
+            reader,
+            header: None,
+            open_telegrams: Vec::new(),
+            finished_telegrams: Vec::new(),
+            telegrams: 0,
+            line: 0,
+            offset: 0,
+            text: String::new(),
+            text_offset: 0,
+            done: false,
+        }
+    }
+
+    /// The header of the stream, once it has been read
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    /// Same as `DsmrError::snippet`, using the text of the telegram that was being read when the error occurred
+    pub fn snippet(&self, error: &DsmrError) -> Option<String> {
+        error.snippet_from(&self.text, self.text_offset)
+    }
+
+    fn finish(&mut self) -> Option<Result<Telegram, DsmrError>> {
+        self.done = true;
+        if self.header.is_none() {
+            return Some(Err(DsmrError::HeaderError("No header found".to_string())));
+        }
+        let (_, telegram) = self.open_telegrams.last()?;
+        Some(Err(DsmrError::MissingField("No 1.2.0 field at the end of the last telegram".to_string()).at(&telegram.span)))
+    }
+
+    fn read_line(&mut self) -> Result<bool, DsmrError> {
+        let mut buffer = Vec::new();
+        if self.reader.read_until(b'\n', &mut buffer)? == 0 {
+            return Ok(false);
+        }
+        let raw_line = String::from_utf8_lossy(&buffer);
+        if self.open_telegrams.is_empty() {
+            self.text.clear();
+            self.text_offset = self.offset;
+        }
+        self.text.push_str(&raw_line);
+        self.line += 1;
+        let line = raw_line.trim_end_matches('\n').trim_end_matches('\r');
+        let span = Span {
+            offset: self.offset,
+            line: self.line,
+            column: 1,
+            telegram: self.telegrams + 1,
+            depth: self.open_telegrams.len().saturating_sub(1),
+        };
+        self.offset += buffer.len();
+        if let Some(header) = &self.header {
+            parse_line(line, &span, header, &mut self.finished_telegrams, &mut self.open_telegrams).map_err(|e| e.at(&span))?;
+        } else if !line.trim().is_empty() {
+            // The first line that is not empty is the header
+            self.header = Some(parse_header(line, &span)?);
+        }
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> Iterator for TelegramReader<R> {
+    type Item = Result<Telegram, DsmrError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let (Some(telegram), Some(header)) = (self.finished_telegrams.pop(), &self.header) {
+                self.telegrams += 1;
+                let result = validate_telegram(&telegram, &format!("telegram {}", self.telegrams))
+                    .and_then(|_| build_telegram(telegram, header));
+                self.done = result.is_err();
+                return Some(result);
+            }
+            if self.done {
+                return None;
+            }
+            match self.read_line() {
+                Ok(true) => {},
+                Ok(false) => return self.finish(),
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
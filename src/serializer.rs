@@ -0,0 +1,125 @@
+//! # Serializer
+//! Writes telegrams back out as the text of a .dsmr file.
+//! Parsing the text gives back the same telegrams, capacities of the counter registers included.
+use crate::telegram::{EventLogEntry, Extensions, Header, Information, Severity, Telegram, Versions};
+
+/// Writes the header and every telegram, children nested inside their parents.
+/// The header is taken from the first telegram, so there is nothing to write without telegrams.
+pub fn serialize(telegrams: &[Telegram]) -> String {
+    let mut text = String::new();
+    if let Some(first) = telegrams.first() {
+        text.push_str(&header_line(&Header { version: first.telegram_version.clone(), extensions: first.telegram_extensions.clone() }));
+        text.push('\n');
+    }
+    for telegram in telegrams {
+        text.push_str(&serialize_telegram(telegram));
+    }
+    text
+}
+
+/// Writes one top-level telegram without a header, for appending to a stream that already has one
+pub fn serialize_telegram(telegram: &Telegram) -> String {
+    let mut lines = Vec::new();
+    telegram_lines(telegram, 0, &mut lines);
+    lines.into_iter().map(|line| line + "\n").collect()
+}
+
+/// The first line of a .dsmr file, like `/v12\+gr`
+pub fn header_line(header: &Header) -> String {
+    let version = match header.version {
+        Versions::V10 => "10",
+        Versions::V12 => "12",
+    };
+    let letters: String = header.extensions.iter().map(|extension| match extension {
+        Extensions::Gas => 'g',
+        Extensions::Recursive => 'r',
+    }).collect();
+    match letters.as_str() {
+        "" => format!("/v{version}\\"),
+        letters => format!("/v{version}\\+{letters}"),
+    }
+}
+
+// `n` is 0 for a top-level telegram and the place among its siblings for a child telegram
+fn telegram_lines(telegram: &Telegram, n: usize, lines: &mut Vec<String>) {
+    lines.push(format!("1.1.{n}#(START)"));
+    lines.push(format!("2.1#({})", telegram.telegram_date));
+    for entry in &telegram.event_log {
+        event_log_lines(entry, lines);
+    }
+    match &telegram.information {
+        Information::Electricity { phases, consumed, produced, consumed_capacity, produced_capacity } => {
+            lines.push("4.1#(E)".to_string());
+            for (index, phase) in phases.iter().enumerate() {
+                lines.push(format!("7.1.{}#({}*V)", index + 1, phase.voltage));
+            }
+            for (index, phase) in phases.iter().enumerate() {
+                lines.push(format!("7.2.{}#({}*A)", index + 1, phase.current));
+            }
+            for (index, phase) in phases.iter().enumerate() {
+                lines.push(format!("7.3.{}#({}*kW)", index + 1, phase.power));
+            }
+            lines.push(format!("7.4.1#({})", energy_register(*consumed, *consumed_capacity)));
+            lines.push(format!("7.4.2#({})", energy_register(*produced, *produced_capacity)));
+        }
+        Information::Water { litres, capacity } => {
+            lines.push("4.1#(W)".to_string());
+            lines.push(format!("6.1#({}*L)", register(*litres as f64, *capacity as f64)));
+        }
+        Information::Gas { model, m3, capacity } => {
+            lines.push("4.1#(G)".to_string());
+            lines.push(format!("5.1#({model})"));
+            let reading = reading_of(*m3, model.factor());
+            lines.push(format!("5.2#({}*m3)", register(reading, capacity / model.factor())));
+        }
+    }
+    for (index, child_telegram) in telegram.child_telegrams.iter().enumerate() {
+        telegram_lines(child_telegram, index + 1, lines);
+    }
+    lines.push(format!("1.2.{n}#(END)"));
+}
+
+fn event_log_lines(entry: &EventLogEntry, lines: &mut Vec<String>) {
+    let severity = match entry.severity {
+        Severity::High => "H",
+        Severity::Low => "L",
+    };
+    let message: String = entry.message.bytes().map(|byte| format!("{byte:02x}")).collect();
+    lines.push(format!("3.1.{}#({severity})", entry.id));
+    lines.push(format!("3.2.{}#({message})", entry.id));
+    lines.push(format!("3.3.{}#({})", entry.id, entry.date));
+}
+
+/// Writes `value` with as many digits in front of the decimal point as the register has, see `register_capacity`.
+fn register(value: f64, capacity: f64) -> String {
+    let digits = capacity.log10().round() as usize;
+    let text = value.abs().to_string();
+    let (whole, fraction) = match text.split_once('.') {
+        Some((whole, fraction)) => (whole, format!(".{fraction}")),
+        None => (text.as_str(), String::new()),
+    };
+    let whole = if digits == 0 && whole == "0" { "" } else { whole };
+    let sign = if value.is_sign_negative() && value != 0.0 { "-" } else { "" };
+    format!("{sign}{whole:0>digits$}{fraction}")
+}
+
+/// Writes an energy register in kWh, unless it was read in Wh with fewer than 3 digits, like `7.4.1#(1*Wh)`.
+/// Such a register wraps below 1 kWh, which has no digits in front of the decimal point to keep its width in.
+fn energy_register(kwh: f64, capacity: f64) -> String {
+    const WH: f64 = 0.001;
+    if capacity < 1.0 {
+        format!("{}*Wh", register(reading_of(kwh, WH), capacity / WH))
+    } else {
+        format!("{}*kWh", register(kwh, capacity))
+    }
+}
+
+/// The reading that the parser turns into `value` when it multiplies it by `factor`, like a 5.2 reading by its gas model
+fn reading_of(value: f64, factor: f64) -> f64 {
+    let reading = value / factor;
+    // Dividing can be one step off from the value that was multiplied
+    [reading, f64::from_bits(reading.to_bits() + 1), f64::from_bits(reading.to_bits().wrapping_sub(1))]
+        .into_iter()
+        .find(|candidate| candidate * factor == value)
+        .unwrap_or(reading)
+}
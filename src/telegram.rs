@@ -0,0 +1,193 @@
+//! # Typed telegram model
+//! What a telegram looks like once it has been parsed and validated.
+use std::collections::BTreeSet;
+use derive_more::Display;
+use tudelft_dsmr_output_generator::date_to_timestamp;
+use crate::error::DsmrError;
+
+#[derive(Debug, PartialEq, Clone, Display)]
+pub enum Versions {
+    V10,
+    V12,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Extensions {
+    Gas, // g
+    Recursive, // r
+}
+#[derive(Debug, PartialEq, Clone)]
+pub struct Header {
+    pub version: Versions, // /vXX\
+    pub extensions: BTreeSet<Extensions>, // +g, +r or +gr, in any order
+}
+
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+pub struct Phase {
+    pub voltage: f64, // 7.1.n
+    pub current: f64, // 7.2.n
+    pub power: f64, // 7.3.n
+}
+/// Whether a time was written down in summer (daylight saving) or winter time.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Season {
+    Summer, // (S), UTC+2
+    Winter, // (W), UTC+1
+}
+/// A date and time as written in 2.1 and 3.3.n fields: `23-Jul-05 15:26:41 (S)`.
+/// Can only be created for a date that exists on the calendar.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DsmrDateTime {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    season: Season,
+}
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+impl DsmrDateTime {
+    /// `year` is the full year, `month` starts at 1 for January
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8, season: Season) -> Result<DsmrDateTime, DsmrError> {
+        let leap_year = year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+        let days_in_month = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if leap_year => 29,
+            2 => 28,
+            other => return Err(DsmrError::InvalidDate(format!("There is no month {other}"))),
+        };
+        if day == 0 || day > days_in_month {
+            return Err(DsmrError::InvalidDate(format!("{} has no day {day} in {year}", MONTHS[month as usize - 1])));
+        }
+        if hour > 23 || minute > 59 || second > 59 {
+            return Err(DsmrError::InvalidDate(format!("{hour:02}:{minute:02}:{second:02} is not a time of day")));
+        }
+        Ok(DsmrDateTime { year, month, day, hour, minute, second, season })
+    }
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+    pub fn second(&self) -> u8 {
+        self.second
+    }
+    pub fn season(&self) -> Season {
+        self.season
+    }
+    /// Seconds the local time is ahead of UTC: 2 hours in summer time, 1 hour in winter time
+    pub fn utc_offset(&self) -> i64 {
+        match self.season {
+            Season::Summer => 7200,
+            Season::Winter => 3600,
+        }
+    }
+    /// Seconds since the Unix epoch, as used by tudelft-dsmr-output-generator
+    pub fn timestamp(&self) -> Result<i64, DsmrError> {
+        let dst = self.season == Season::Summer;
+        date_to_timestamp(self.year, self.month, self.day, self.hour, self.minute, self.second, dst)
+            .ok_or_else(|| DsmrError::InvalidDate(format!("Cannot convert {self} to a timestamp")))
+    }
+}
+
+// Written the same way as in a telegram
+impl std::fmt::Display for DsmrDateTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let season = match self.season {
+            Season::Summer => 'S',
+            Season::Winter => 'W',
+        };
+        write!(
+            f, "{:02}-{}-{:02} {:02}:{:02}:{:02} ({season})",
+            self.year % 100, MONTHS[self.month as usize - 1], self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Parses a date written as in a telegram, see `Display`
+impl std::str::FromStr for DsmrDateTime {
+    type Err = DsmrError;
+    fn from_str(input: &str) -> Result<DsmrDateTime, DsmrError> {
+        crate::parser::parse_datetime(input)
+    }
+}
+
+/// How serious an event is, from the 3.1.n field. Ordered from least to most severe.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Display)]
+pub enum Severity {
+    Low, // L
+    High, // H
+}
+/// Model of a gas meter, from the 5.1 field. Each model counts its 5.2 reading in its own unit.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Display)]
+pub enum GasModel {
+    G4,
+    G5,
+    G6,
+}
+
+impl GasModel {
+    /// Every known model, written as in a 5.1 field
+    pub const ALL: [GasModel; 3] = [GasModel::G4, GasModel::G5, GasModel::G6];
+
+    /// How many m3 one unit of a 5.2 reading of this model stands for
+    pub fn factor(self) -> f64 {
+        match self {
+            GasModel::G4 => 1.0,
+            GasModel::G5 => 10.0,
+            GasModel::G6 => 100.0,
+        }
+    }
+
+    /// Converts a 5.2 reading of this model to m3
+    pub fn to_m3(self, reading: f64) -> f64 {
+        reading * self.factor()
+    }
+}
+/// Parses a model written as in a 5.1 field
+impl std::str::FromStr for GasModel {
+    type Err = DsmrError;
+    fn from_str(input: &str) -> Result<GasModel, DsmrError> {
+        crate::parser::gas_model(input)
+    }
+}
+/// One entry of the event log, made up of the 3.1.n, 3.2.n and 3.3.n fields with the same n.
+#[derive(Debug, PartialEq, Clone)]
+pub struct EventLogEntry {
+    pub id: u32, // n
+    pub severity: Severity, // 3.1.n
+    pub message: String, // 3.2.n, decoded from hex
+    pub date: DsmrDateTime, // 3.3.n
+}
+/// The information of exactly one meter, chosen by the 4.1 information type.
+#[derive(Debug, PartialEq)]
+pub enum Information {
+    // The capacities are where the fixed-width 7.4.1, 7.4.2, 6.1 and 5.2 registers wrap back to zero
+    Electricity { phases: [Phase; 3], consumed: f64, produced: f64, consumed_capacity: f64, produced_capacity: f64 }, // 4.1#(E)
+    Water { litres: u64, capacity: u64 }, // 4.1#(W)
+    Gas { model: GasModel, m3: f64, capacity: f64 }, // 4.1#(G), m3 and capacity already converted by the model
+}
+#[derive(Debug, PartialEq)]
+pub struct Telegram {
+    pub telegram_version: Versions, // 0.0
+    pub telegram_extensions: BTreeSet<Extensions>, // 0.0+
+    pub telegram_date: DsmrDateTime, // 2.1
+    pub time_stamp: i64, // 2.1
+    pub event_log: Vec<EventLogEntry>, // 3.x.n
+    pub information: Information, // 4.1
+    pub child_telegrams: Vec<Telegram>, // 1.1.n ... 1.2.n
+}
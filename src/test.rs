@@ -1,6 +1,28 @@
-#[cfg(test)]
-use crate::parse_datetime;
-use super::*;
+use std::collections::BTreeSet;
+use proptest::prelude::*;
+use tudelft_dsmr_output_generator::current_over_time::CurrentData;
+use tudelft_dsmr_output_generator::energy_over_time::EnergyData;
+use tudelft_dsmr_output_generator::gas_over_time::GasData;
+use tudelft_dsmr_output_generator::voltage_over_time::VoltageData;
+use tudelft_dsmr_output_generator::water_over_time::WaterData;
+use crate::aggregate::*;
+use crate::counters::*;
+use crate::error::{DsmrError, Span};
+use crate::faults::*;
+use crate::parser::*;
+use crate::follow::Follow;
+use crate::generator::*;
+use crate::reader::TelegramReader;
+use crate::serializer::*;
+use crate::telegram::*;
+
+/// Drops the location from an error, so tests can match on the reason alone
+fn into_reason(error: DsmrError) -> DsmrError {
+    match error {
+        DsmrError::Spanned(_, e) => *e,
+        other => other,
+    }
+}
 // #[test]
 // fn test_telegram_ver_pass() {
 //     let input = "12";
@@ -17,16 +39,16 @@ use super::*;
 
 #[test]
 fn test_version_extension_pass() {
-    let input = "gr";
+    let input = 'g';
     let result = version_ext(input);
-    assert_eq!(result, Ok(Extensions::GasRecursive));
+    assert!(matches!(result, Ok(Extensions::Gas)));
 }
 
 #[test]
 fn test_version_extension_2() {
-    let input = "ab";
+    let input = 'a';
     let result = version_ext(input);
-    assert_ne!(result, Ok(Extensions::Recursive));
+    assert!(matches!(result, Err(DsmrError::ExtensionError(_))));
 }
 
 #[test]
@@ -61,27 +83,27 @@ fn test_process_lines_fail2() {
 fn test_version_key_pass() {
     let line = "1.1.0";
     let result = version_key(line);
-    assert_eq!(result, (Ok(Keys::Start)));
+    assert!(matches!(result, Ok(Keys::Start)));
 }
 
 #[test]
 fn test_version_key_fail() {
     let line = "1.1.1";
     let result = version_key(line);
-    assert_ne!(result, (Ok(Keys::Start)));
+    assert!(!matches!(result, Ok(Keys::Start)));
 }
 
 #[test]
 fn test_hex_string_pass() {
     let line = "506f776572204661696c757265";
-    let result = hex_string(line);
+    let result = hex_string(line).unwrap();
     assert_eq!(result, "Power Failure");
 }
 
 #[test] //
 fn test_hex_string_fail() {
     let line = "506f776572204661696c757265";
-    let result = hex_string(line);
+    let result = hex_string(line).unwrap();
     assert_ne!(result, "Power Failure.");
 }
 
@@ -89,27 +111,54 @@ fn test_hex_string_fail() {
 fn test_parse_datetime_jan_pass() {
     let date_message = "22-Jan-22 12:34:56 (S)";
     let result = parse_datetime(date_message);
-    assert_eq!(result, Some((2022, 1, 22, 12, 34, 56, true)));
+    assert_eq!(result.unwrap(), DsmrDateTime::new(2022, 1, 22, 12, 34, 56, Season::Summer).unwrap());
 }
 #[test]
 fn test_parse_datetime_apr_pass() {
     let date_message = "22-Apr-22 12:34:56 (S)";
     let result = parse_datetime(date_message);
-    assert_eq!(result, Some((2022, 4, 22, 12, 34, 56, true)));
+    assert_eq!(result.unwrap(), DsmrDateTime::new(2022, 4, 22, 12, 34, 56, Season::Summer).unwrap());
 }
 
 #[test]
 fn test_parse_datetime_fail() {
     let date_message = "22-Jan-22 12:34:56 (S)";
     let result = parse_datetime(date_message);
-    assert_ne!(result, Some((22, 1, 22, 12, 34, 56, true)));
+    assert_ne!(result.unwrap().year(), 22);
+}
+
+/// Readings of one meter at the top of the tree, with `information` read at each time stamp
+fn meter_readings(values: impl IntoIterator<Item = (i64, Information)>) -> Readings {
+    let mut readings = Readings::default();
+    for (time_stamp, information) in values {
+        readings.add(&Telegram {
+            telegram_version: Versions::V12,
+            telegram_extensions: BTreeSet::new(),
+            telegram_date: DsmrDateTime::new(2023, 7, 5, 0, 0, 0, Season::Summer).unwrap(),
+            time_stamp,
+            event_log: Vec::new(),
+            information,
+            child_telegrams: Vec::new(),
+        });
+    }
+    readings
+}
+fn water(litres: u64) -> Information {
+    Information::Water { litres, capacity: 10_000_000_000 }
+}
+fn gas(m3: f64) -> Information {
+    Information::Gas { model: GasModel::G4, m3, capacity: 100_000.0 }
+}
+fn electricity(produced: f64, consumed: f64) -> Information {
+    Information::Electricity { phases: [Phase::default(); 3], consumed, produced, consumed_capacity: 10_000_000_000.0, produced_capacity: 10_000_000_000.0 }
 }
 
 #[test]
 fn test_vector_water_pass() {
     let consumed = vec![0];
     let time = vec![1072914282];
-    let result = vector_water(consumed, time);
+    let readings = meter_readings(time.into_iter().zip(consumed.into_iter().map(water)));
+    let result = vector_water(&readings, 0.1, Window::default());
 
     // Convert vectors to strings for comparison
     let expected_str = format!("{:?}", vec![WaterData { water_delta: 0, timestamp: 1072914282 }]);
@@ -122,7 +171,8 @@ fn test_vector_water_pass() {
 fn test_vector_water_fail() {
     let consumed = vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14];
     let time = vec![1072914282, 1072914283, 1072914284, 1072914285, 1072914286, 1072914287, 1072914288, 1072914289, 1072914290, 1072914291, 1072914292, 1072914293, 1072914294, 1072914295];
-    let result = vector_water(consumed, time);
+    let readings = meter_readings(time.into_iter().zip(consumed.into_iter().map(water)));
+    let result = vector_water(&readings, 0.1, Window::default());
 
     // Convert vectors to strings for comparison
     let expected_str = format!("{:?}", vec![WaterData { water_delta: 0, timestamp: 1072914282 }]);
@@ -136,7 +186,8 @@ fn test_vector_energy_pass() {
     let produced = vec![0.0];
     let consumed = vec![0.0];
     let time = vec![1072914282];
-    let result = vector_energy(produced, consumed, time);
+    let readings = meter_readings(time.into_iter().zip(produced.into_iter().zip(consumed).map(|(produced, consumed)| electricity(produced, consumed))));
+    let result = vector_energy(&readings, 0.1, Window::default());
 
     // Convert vectors to strings for comparison
     let expected_str = format!("{:?}", vec![EnergyData { produced: 0.0, consumed: 0.0, timestamp: 1072914282 }]);
@@ -150,7 +201,8 @@ fn test_vector_energy_fail() {
     let produced = vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 13.0, 14.0];
     let consumed = vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 13.0, 14.0];
     let time = vec![1072914282, 1072914283, 1072914284, 1072914285, 1072914286, 1072914287, 1072914288, 1072914289, 1072914290, 1072914291, 1072914292, 1072914293, 1072914294, 1072914295];
-    let result = vector_energy(produced, consumed, time);
+    let readings = meter_readings(time.into_iter().zip(produced.into_iter().zip(consumed).map(|(produced, consumed)| electricity(produced, consumed))));
+    let result = vector_energy(&readings, 0.1, Window::default());
 
     // Convert vectors to strings for comparison
     let expected_str = format!("{:?}", vec![EnergyData { produced: 0.0, consumed: 0.0, timestamp: 1072914282 }]);
@@ -163,7 +215,8 @@ fn test_vector_energy_fail() {
 fn test_vector_gas_pass() {
     let consumed = vec![0.0];
     let time = vec![1072914282];
-    let result = vector_gas(consumed, time);
+    let readings = meter_readings(time.into_iter().zip(consumed.into_iter().map(gas)));
+    let result = vector_gas(&readings, 0.1, Window::default());
 
     // Convert vectors to strings for comparison
     let expected_str = format!("{:?}", vec![GasData { gas_delta: 0.0, timestamp: 1072914282 }]);
@@ -176,7 +229,8 @@ fn test_vector_gas_pass() {
 fn test_vector_gas_fail() {
     let consumed = vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 13.0, 14.0];
     let time = vec![1072914282, 1072914283, 1072914284, 1072914285, 1072914286, 1072914287, 1072914288, 1072914289, 1072914290, 1072914291, 1072914292, 1072914293, 1072914294, 1072914295];
-    let result = vector_gas(consumed, time);
+    let readings = meter_readings(time.into_iter().zip(consumed.into_iter().map(gas)));
+    let result = vector_gas(&readings, 0.1, Window::default());
 
     // Convert vectors to strings for comparison
     let expected_str = format!("{:?}", vec![GasData { gas_delta: 0.0, timestamp: 1072914282 }]);
@@ -187,12 +241,12 @@ fn test_vector_gas_fail() {
 #[test]
 fn test_handle_voltage_p1_pass() {
     // Arrange
-    let mut telegram = Telegram::default();
+    let mut telegram = TelegramFields::default();
     let payload = "123.45*V";
     let key = "voltage_p1";
 
     // Act
-    handle_voltage(payload, &mut telegram, key);
+    handle_voltage(payload, &mut telegram, key).expect("valid payload");
 
     // Assert
     assert_eq!(telegram.voltage_p1, vec![123.45]);
@@ -202,12 +256,12 @@ fn test_handle_voltage_p1_pass() {
 #[test]
 fn test_handle_voltage_p2_pass() {
     // Arrange
-    let mut telegram = Telegram::default();
+    let mut telegram = TelegramFields::default();
     let payload = "123.45*V";
     let key = "voltage_p2";
 
     // Act
-    handle_voltage(payload, &mut telegram, key);
+    handle_voltage(payload, &mut telegram, key).expect("valid payload");
 
     // Assert
     assert_eq!(telegram.voltage_p2, vec![123.45]);
@@ -217,12 +271,12 @@ fn test_handle_voltage_p2_pass() {
 #[test]
 fn test_handle_voltage_p3_pass() {
     // Arrange
-    let mut telegram = Telegram::default();
+    let mut telegram = TelegramFields::default();
     let payload = "123.45*V";
     let key = "voltage_p3";
 
     // Act
-    handle_voltage(payload, &mut telegram, key);
+    handle_voltage(payload, &mut telegram, key).expect("valid payload");
 
     // Assert
     assert_eq!(telegram.voltage_p3, vec![123.45]);
@@ -232,12 +286,12 @@ fn test_handle_voltage_p3_pass() {
 #[test]
 fn test_handle_current_p1_pass() {
     // Arrange
-    let mut telegram = Telegram::default();
+    let mut telegram = TelegramFields::default();
     let payload = "123.45*A";
     let key = "current_p1";
 
     // Act
-    handle_current(payload, &mut telegram, key);
+    handle_current(payload, &mut telegram, key).expect("valid payload");
 
     // Assert
     assert_eq!(telegram.current_p1, vec![123.45]);
@@ -247,12 +301,12 @@ fn test_handle_current_p1_pass() {
 #[test]
 fn test_handle_current_p2_pass() {
     // Arrange
-    let mut telegram = Telegram::default();
+    let mut telegram = TelegramFields::default();
     let payload = "123.45*A";
     let key = "current_p2";
 
     // Act
-    handle_current(payload, &mut telegram, key);
+    handle_current(payload, &mut telegram, key).expect("valid payload");
 
     // Assert
     assert_eq!(telegram.current_p2, vec![123.45]);
@@ -262,12 +316,12 @@ fn test_handle_current_p2_pass() {
 #[test]
 fn test_handle_current_p3_pass() {
     // Arrange
-    let mut telegram = Telegram::default();
+    let mut telegram = TelegramFields::default();
     let payload = "123.45*A";
     let key = "current_p3";
 
     // Act
-    handle_current(payload, &mut telegram, key);
+    handle_current(payload, &mut telegram, key).expect("valid payload");
 
     // Assert
     assert_eq!(telegram.current_p3, vec![123.45]);
@@ -277,12 +331,12 @@ fn test_handle_current_p3_pass() {
 #[test]
 fn test_handle_power_p1_pass() {
     // Arrange
-    let mut telegram = Telegram::default();
+    let mut telegram = TelegramFields::default();
     let payload = "123.45*kW";
     let key = "power_p1";
 
     // Act
-    handle_power(payload, &mut telegram, key);
+    handle_power(payload, &mut telegram, key).expect("valid payload");
 
     // Assert
     assert_eq!(telegram.power_p1, vec![123.45]);
@@ -292,12 +346,12 @@ fn test_handle_power_p1_pass() {
 #[test]
 fn test_handle_power_p2_pass() {
     // Arrange
-    let mut telegram = Telegram::default();
+    let mut telegram = TelegramFields::default();
     let payload = "123.45*kW";
     let key = "power_p2";
 
     // Act
-    handle_power(payload, &mut telegram, key);
+    handle_power(payload, &mut telegram, key).expect("valid payload");
 
     // Assert
     assert_eq!(telegram.power_p2, vec![123.45]);
@@ -307,12 +361,12 @@ fn test_handle_power_p2_pass() {
 #[test]
 fn test_handle_power_p3_pass() {
     // Arrange
-    let mut telegram = Telegram::default();
+    let mut telegram = TelegramFields::default();
     let payload = "123.45*kW";
     let key = "power_p3";
 
     // Act
-    handle_power(payload, &mut telegram, key);
+    handle_power(payload, &mut telegram, key).expect("valid payload");
 
     // Assert
     assert_eq!(telegram.power_p3, vec![123.45]);
@@ -321,12 +375,12 @@ fn test_handle_power_p3_pass() {
 }
 #[test]
 fn test_handle_energy_consumption() {
-    let mut telegram = Telegram::default(); // Assuming you have a default implementation for Telegram
+    let mut telegram = TelegramFields::default(); // Assuming you have a default implementation for Telegram
 
     // Simulate an energy consumption payload
     let payload = "123.45*kWh";
     let key = "energy_consumption";
-    handle_energy(payload, &mut telegram, key);
+    handle_energy(payload, &mut telegram, key).expect("valid payload");
 
     // Assert that the parsed value is correctly added to the energy_consumption vector
     assert_eq!(telegram.energy_consumption, vec![123.45]);
@@ -334,56 +388,52 @@ fn test_handle_energy_consumption() {
 
 #[test]
 fn test_handle_energy_production() {
-    let mut telegram = Telegram::default(); // Assuming you have a default implementation for Telegram
+    let mut telegram = TelegramFields::default(); // Assuming you have a default implementation for Telegram
 
     // Simulate an energy production payload
     let payload = "67.89*kWh";
     let key = "energy_production";
-    handle_energy(payload, &mut telegram, key);
+    handle_energy(payload, &mut telegram, key).expect("valid payload");
 
     // Assert that the parsed value is correctly added to the energy_production vector
     assert_eq!(telegram.energy_production, vec![67.89]);
 }
 #[test]
-fn test_handle_gas_consumption_g4() {
-    // Test case 1: last_GasModel is "G4", child_telegram is "100"
-    let mut child_telegram1 = ChildTelegram1::default();
-    let mut telegram = Telegram::default();
-    handle_gas_consumption(5.0, "G4", &mut child_telegram1, &mut telegram, "100");
-    assert_eq!(child_telegram1.gas_consumption, vec![5.0]);
+fn test_gas_model_conversion() {
+    // G4 counts in m3, G5 in tens of m3 and G6 in hundreds
+    let converted: Vec<f64> = GasModel::ALL.iter().map(|model| model.to_m3(5.0)).collect();
+    assert_eq!(converted, vec![5.0, 50.0, 500.0]);
+    assert_eq!(gas_model("G5").unwrap(), GasModel::G5);
 }
 #[test]
-fn test_handle_gas_consumption_g5() {
-    // Test case 1: last_GasModel is "G5", child_telegram is "100"
-    let mut child_telegram1 = ChildTelegram1::default();
-    let mut telegram = Telegram::default();
-    handle_gas_consumption(5.0, "G5", &mut child_telegram1, &mut telegram, "100");
-    assert_eq!(child_telegram1.gas_consumption, vec![50.0]);
+fn test_handle_gas_consumption_keeps_reading() {
+    let mut child_telegram1 = TelegramFields::default();
+    handle_gas_consumption("00005.000*m3", &mut child_telegram1).unwrap();
+    assert_eq!(child_telegram1.gas_consumption, vec![5.0]);
+    assert_eq!(child_telegram1.gas_capacity, vec![100_000.0]);
 }
 #[test]
-fn test_handle_gas_consumption_g6() {
-    // Test case 1: last_GasModel is "G6", child_telegram is "100"
-    let mut child_telegram1 = ChildTelegram1::default();
-    let mut telegram = Telegram::default();
-    handle_gas_consumption(5.0, "G6", &mut child_telegram1, &mut telegram, "100");
-    assert_eq!(child_telegram1.gas_consumption, vec![500.0]);
+fn test_parse_gas_model_after_reading() {
+    // The 5.2 reading is converted by the model of its own telegram, wherever the 5.1 line is
+    let input = include_str!("../examples/good/simple_gas.dsmr").replacen("5.1#(G4)\n5.2#(12345.123*m3)", "5.2#(12345.123*m3)\n5.1#(G6)", 1);
+    let telegrams = parse(&input).unwrap();
+    assert_eq!(telegrams[0].child_telegrams[0].information, Information::Gas { model: GasModel::G6, m3: 1234512.3, capacity: 10_000_000.0 });
 }
 #[test]
 fn test_parse_datetime() {
-    // Test case with a valid input
-    let valid_date = "2023-11-15 12:34:56 (S)";
-    assert_ne!(parse_datetime(valid_date), Some((2023, 11, 15, 12, 34, 56, true)));
+    // ISO dates are not how telegrams write them
+    let iso_date = "2023-11-15 12:34:56 (S)";
+    assert!(matches!(parse_datetime(iso_date), Err(DsmrError::InvalidDate(_))));
 }
 #[test]
 fn test_vector_voltage() {
     // Sample input data
-    let voltage_p1 = vec![0.0];
-    let voltage_p2 = vec![0.0];
-    let voltage_p3 = vec![0.0];
-    let time_stamp = vec![1234567890];
+    let voltage_p1 = vec![(1234567890, 0.0)];
+    let voltage_p2 = vec![(1234567890, 0.0)];
+    let voltage_p3 = vec![(1234567890, 0.0)];
 
     // Call the function being tested
-    let result = vector_voltage(voltage_p1, voltage_p2, voltage_p3, time_stamp);
+    let result = vector_voltage(voltage_p1, voltage_p2, voltage_p3, Gaps::Skip);
     let result_str = format!("{:?}", result);
     let expected_str = format!("{:?}", vec![VoltageData {phase_1: 0.0, phase_2: 0.0, phase_3: 0.0, timestamp: 1234567890}]);
 
@@ -393,13 +443,12 @@ fn test_vector_voltage() {
 #[test]
 fn test_vector_current() {
     // Sample input data
-    let current_p1 = vec![0.0];
-    let current_p2 = vec![0.0];
-    let current_p3 = vec![0.0];
-    let time_stamp = vec![1234567890];
+    let current_p1 = vec![(1234567890, 0.0)];
+    let current_p2 = vec![(1234567890, 0.0)];
+    let current_p3 = vec![(1234567890, 0.0)];
 
     // Call the function being tested
-    let result = vector_current(current_p1, current_p2, current_p3, time_stamp);
+    let result = vector_current(current_p1, current_p2, current_p3, Gaps::Skip);
     let result_str = format!("{:?}", result);
     let expected_str = format!("{:?}", vec![CurrentData {phase_1: 0.0, phase_2: 0.0, phase_3: 0.0, timestamp: 1234567890}]);
 
@@ -409,7 +458,1284 @@ fn test_vector_current() {
 #[test]
 fn test_handle_water_pass() {
     let payload = "1234*L";
-    let mut telegram = Telegram::default();
-    handle_water(payload, &mut telegram);
+    let mut telegram = TelegramFields::default();
+    handle_water(payload, &mut telegram).expect("valid payload");
     assert_eq!(telegram.water_consumption, vec![1234]);
-}
\ No newline at end of file
+}
+#[test]
+fn test_parse_electricity_telegram() {
+    let input = include_str!("../examples/good/reorder.dsmr");
+    let telegrams = parse(input).expect("reorder.dsmr should parse");
+    assert_eq!(telegrams.len(), 1);
+    match &telegrams[0].information {
+        Information::Electricity { phases, consumed, produced, .. } => {
+            assert_eq!(phases[0], Phase { voltage: 241.7, current: 1.0, power: 1.0 });
+            assert_eq!(phases[1], Phase { voltage: 240.6, current: 10.0, power: -5.01 });
+            assert_eq!(phases[2], Phase { voltage: 241.92, current: 0.5, power: 2.5 });
+            assert_eq!(*consumed, 11454892.0);
+            assert_eq!(*produced, 1245.0);
+        }
+        other => panic!("Expected electricity, got {:?}", other),
+    }
+}
+#[test]
+fn test_parse_water_with_gas_child() {
+    let input = include_str!("../examples/good/simple_gas.dsmr");
+    let telegrams = parse(input).expect("simple_gas.dsmr should parse");
+    assert_eq!(telegrams[0].information, Information::Water { litres: 12, capacity: 10_000_000_000 });
+    assert_eq!(telegrams[0].child_telegrams.len(), 1);
+    let child = &telegrams[0].child_telegrams[0];
+    assert_eq!(child.information, Information::Gas { model: GasModel::G4, m3: 12345.123, capacity: 100_000.0 });
+}
+#[test]
+fn test_parse_two_packets() {
+    let input = include_str!("../examples/good/two_packets.dsmr");
+    let telegrams = parse(input).expect("two_packets.dsmr should parse");
+    assert_eq!(telegrams.len(), 2);
+}
+#[test]
+fn test_parse_mixed_meter_fail() {
+    let input = "/v10\\\n1.1.0#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n4.1#(W)\n6.1#(0000000012*L)\n7.1.1#(0241.7*V)\n1.2.0#(END)\n";
+    assert!(matches!(parse(input).map_err(into_reason), Err(DsmrError::MixedMeter(_))));
+}
+#[test]
+fn test_parse_duplicate_information_type_fail() {
+    let input = include_str!("../examples/bad/duplicate_info.dsmr");
+    assert!(matches!(parse(input).map_err(into_reason), Err(DsmrError::InformationTypeCount(_))));
+}
+#[test]
+fn test_parse_missing_electricity_fail() {
+    let input = include_str!("../examples/bad/missing_electricity.dsmr");
+    assert!(matches!(parse(input).map_err(into_reason), Err(DsmrError::MissingField(_))));
+}
+#[test]
+fn test_collect_readings_includes_child() {
+    let input = include_str!("../examples/good/simple_gas.dsmr");
+    let telegrams = parse(input).expect("simple_gas.dsmr should parse");
+    let readings = collect_readings(&telegrams);
+    let time_stamp = telegrams[0].time_stamp;
+    assert_eq!(readings.counter("6.1"), vec![vec![(time_stamp, 12.0)]]);
+    assert_eq!(readings.counter("5.2"), vec![vec![(time_stamp, 12345.123)]]);
+    assert_eq!(readings.time_stamps(), vec![time_stamp]);
+}
+#[test]
+fn test_collect_readings_merges_same_time_stamp() {
+    // Both telegrams were read at 23-Jul-05 15:26:41
+    let input = include_str!("../examples/good/two_packets.dsmr");
+    let telegrams = parse(input).unwrap();
+    let readings = collect_readings(&telegrams);
+    let t = telegrams[0].time_stamp;
+    assert_eq!(readings.time_stamps(), vec![t]);
+    assert_eq!(readings.voltage(), [vec![(t, 242.7)], vec![(t, 241.6)], vec![(t, 242.92)]]);
+    assert_eq!(readings.current(), [vec![(t, 1.0)], vec![(t, 10.0)], vec![(t, 0.5)]]);
+    assert_eq!(readings.power(), [vec![(t, 2.0)], vec![(t, -10.02)], vec![(t, 5.0)]]);
+    // Two top-level telegrams at the same time are two meters, each with a counter of its own
+    assert_eq!(readings.counter("7.4.1"), vec![vec![(t, 11454892.0)], vec![(t, 12454892.0)]]);
+    assert_eq!(readings.counter("7.4.2"), vec![vec![(t, 1245.0)], vec![(t, 1445.0)]]);
+    let [voltage_p1, voltage_p2, voltage_p3] = readings.voltage();
+    assert_eq!(vector_voltage(voltage_p1, voltage_p2, voltage_p3, Gaps::Skip).len(), 1);
+}
+#[test]
+fn test_collect_readings_recursive_sequences() {
+    let inputs = [
+        include_str!("../examples/good_sequences/should_parse_0_recursive.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_1_recursive.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_2_recursive.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_3_recursive.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_4_recursive.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_5_recursive.dsmr"),
+    ];
+    for input in inputs {
+        let telegrams = parse(input).unwrap();
+        let readings = collect_readings(&telegrams);
+        let totals = readings.series(|totals| Some(totals.clone()));
+        for (telegram, (time_stamp, totals)) in telegrams.iter().zip(&totals) {
+            // Water at the top, electricity below it and gas below that, all read at the same moment
+            let electricity = &telegram.child_telegrams[0];
+            let gas = &electricity.child_telegrams[0];
+            let (Information::Electricity { phases, consumed, produced, .. }, Information::Gas { m3, .. }) = (&electricity.information, &gas.information) else {
+                panic!("unexpected meters in {telegram:?}");
+            };
+            assert_eq!(*time_stamp, telegram.time_stamp);
+            assert_eq!(totals.electricity, Some(ElectricityTotals { phases: *phases, consumed: *consumed, produced: *produced }));
+            assert_eq!(totals.gas, Some(*m3));
+            assert!(matches!((totals.water, &telegram.information), (Some(litres), Information::Water { litres: top, .. }) if litres == *top));
+        }
+        // One meter of each kind, at its own place in the tree
+        for field in ["6.1", "7.4.1", "7.4.2", "5.2"] {
+            assert_eq!(readings.counter(field).len(), 1);
+        }
+        assert_eq!(readings.time_stamps().len(), telegrams.len());
+        let [voltage_p1, voltage_p2, voltage_p3] = readings.voltage();
+        assert_eq!(vector_voltage(voltage_p1, voltage_p2, voltage_p3, Gaps::Skip).len(), telegrams.len());
+        let [current_p1, current_p2, current_p3] = readings.current();
+        assert_eq!(vector_current(current_p1, current_p2, current_p3, Gaps::Skip).len(), telegrams.len());
+    }
+}
+#[test]
+fn test_collect_readings_max_and_sum_over_children() {
+    // Give the electricity child of the first telegram a sibling with a higher voltage on phase 1
+    let input = include_str!("../examples/good_sequences/should_parse_5_recursive.dsmr");
+    let first_end = input.find("1.2.0#(END)").unwrap();
+    let electricity_start = input.find("1.1.1#(START)\n4.1#(E)").unwrap();
+    let gas_start = input[electricity_start..].find("1.1.1#(START)\n4.1#(G)").unwrap() + electricity_start;
+    let sibling = input[electricity_start..gas_start].replace("0268.5*V", "0270.0*V").replace("1.1.1#(START)", "1.1.2#(START)") + "1.2.2#(END)\n";
+    let input = format!("{}{}{}", &input[..first_end], sibling, &input[first_end..]);
+    let telegrams = parse(&input).unwrap();
+    assert_eq!(telegrams[0].child_telegrams.len(), 2);
+    let readings = collect_readings(&telegrams[..1]);
+    let first = |series: [Series<f64>; 3]| series.map(|series| series[0].1);
+    assert_eq!(first(readings.voltage()), [270.0, 260.25, 260.0]);
+    assert_eq!(first(readings.current()), [10.0, 10.0, 8.4]);
+    assert_eq!(first(readings.power()), [20.0, 20.0, 4.0]);
+    // The two electricity meters keep counters of their own
+    assert_eq!(readings.counter("7.4.1").len(), 2);
+}
+#[test]
+fn test_parse_recursive_sequences() {
+    let inputs = [
+        include_str!("../examples/good_sequences/should_parse_0_recursive.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_1_recursive.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_2_recursive.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_3_recursive.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_4_recursive.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_5_recursive.dsmr"),
+    ];
+    for input in inputs {
+        let telegrams = parse(input).expect("recursive sequence should parse");
+        for telegram in &telegrams {
+            // Water at the top, with an electricity child that itself has a gas child
+            assert!(matches!(telegram.information, Information::Water { .. }));
+            assert_eq!(telegram.child_telegrams.len(), 1);
+            let electricity = &telegram.child_telegrams[0];
+            assert!(matches!(electricity.information, Information::Electricity { .. }));
+            assert_eq!(electricity.child_telegrams.len(), 1);
+            let gas = &electricity.child_telegrams[0];
+            assert!(matches!(gas.information, Information::Gas { .. }));
+            assert!(gas.child_telegrams.is_empty());
+        }
+    }
+}
+#[test]
+fn test_parse_recursive_sequence_count() {
+    let input = include_str!("../examples/good_sequences/should_parse_0_recursive.dsmr");
+    let telegrams = parse(input).expect("recursive sequence should parse");
+    assert_eq!(telegrams.len(), 100);
+}
+#[test]
+fn test_parse_sibling_children() {
+    let input = "/v12\\+gr\n1.1.0#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n4.1#(W)\n6.1#(0000000012*L)\n\
+        1.1.1#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n4.1#(G)\n5.1#(G4)\n5.2#(00001.000*m3)\n1.2.1#(END)\n\
+        1.1.2#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n4.1#(G)\n5.1#(G5)\n5.2#(00002.000*m3)\n1.2.2#(END)\n\
+        1.2.0#(END)\n";
+    let telegrams = parse(input).expect("sibling children should parse");
+    assert_eq!(telegrams[0].child_telegrams.len(), 2);
+    assert_eq!(telegrams[0].child_telegrams[1].information, Information::Gas { model: GasModel::G5, m3: 20.0, capacity: 1_000_000.0 });
+}
+#[test]
+fn test_parse_child_numbering_fail() {
+    let child = |n: usize| format!("1.1.{n}#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n4.1#(G)\n5.1#(G4)\n5.2#(00001.000*m3)\n1.2.{n}#(END)\n");
+    let telegram = |children: String| format!(
+        "/v12\\+gr\n1.1.0#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n4.1#(W)\n6.1#(0000000012*L)\n{children}1.2.0#(END)\n"
+    );
+    // An only child has to be the first, and siblings count up
+    for children in [child(3), child(1) + &child(1), child(2) + &child(1), child(1) + &child(3)] {
+        assert!(matches!(parse(&telegram(children.clone())).map_err(into_reason), Err(DsmrError::ChildNumbering(_))), "{children}");
+    }
+    assert!(parse(&telegram(child(1) + &child(2) + &child(3))).is_ok());
+    // Reading one telegram at a time checks the same
+    let error = TelegramReader::new(telegram(child(3)).as_bytes()).find_map(Result::err);
+    assert_eq!(error.map(|e| e.kind()), Some("ChildNumbering"));
+}
+#[test]
+fn test_parse_mismatched_child_end_fail() {
+    let input = "/v12\\+gr\n1.1.0#(START)\n1.1.1#(START)\n1.2.2#(END)\n1.2.0#(END)\n";
+    assert!(matches!(parse(input).map_err(into_reason), Err(DsmrError::Nesting(_))));
+}
+#[test]
+fn test_parse_child_outside_telegram_fail() {
+    let input = "/v12\\+gr\n1.1.1#(START)\n1.2.1#(END)\n";
+    assert!(matches!(parse(input).map_err(into_reason), Err(DsmrError::Nesting(_))));
+}
+#[test]
+fn test_parse_unterminated_child_fail() {
+    let input = "/v12\\+gr\n1.1.0#(START)\n1.1.1#(START)\n1.2.0#(END)\n";
+    assert!(matches!(parse(input).map_err(into_reason), Err(DsmrError::Nesting(_))));
+}
+#[test]
+fn test_validate_missing_information_type_fail() {
+    let input = "/v10\\\n1.1.0#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n6.1#(0000000012*L)\n1.2.0#(END)\n";
+    assert!(matches!(parse(input).map_err(into_reason), Err(DsmrError::InformationTypeCount(_))));
+}
+#[test]
+fn test_validate_reports_position() {
+    let mut second = TelegramFields::default();
+    second.information_type.push("W".to_string());
+    second.child_telegrams.push(TelegramFields { number: 1, ..Default::default() });
+    let mut first = TelegramFields::default();
+    first.information_type.push("E".to_string());
+    match validate_telegrams(&[first, second]).map_err(into_reason) {
+        Err(DsmrError::InformationTypeCount(message)) => assert!(message.starts_with("telegram 2, child 1 ")),
+        other => panic!("Expected InformationTypeCount, got {:?}", other),
+    }
+}
+#[test]
+fn test_validate_too_many_children_fail() {
+    let child = "1.1.1#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n4.1#(G)\n5.1#(G4)\n5.2#(00001.000*m3)\n1.2.1#(END)\n";
+    let input = format!(
+        "/v12\\+gr\n1.1.0#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n4.1#(W)\n6.1#(0000000012*L)\n{}1.2.0#(END)\n",
+        child.repeat(4)
+    );
+    assert!(matches!(parse(&input).map_err(into_reason), Err(DsmrError::TooManyChildren(_))));
+}
+#[test]
+fn test_validate_three_children_pass() {
+    let mut telegram = TelegramFields::default();
+    telegram.information_type.push("W".to_string());
+    for number in 1..=3 {
+        let mut child_telegram = TelegramFields { number, ..Default::default() };
+        child_telegram.information_type.push("G".to_string());
+        telegram.child_telegrams.push(child_telegram);
+    }
+    assert!(validate_telegrams(&[telegram]).is_ok());
+}
+#[test]
+fn test_version_key_invalid_fail() {
+    assert!(matches!(version_key("9.9"), Err(DsmrError::KeyError(_))));
+}
+#[test]
+fn test_handle_voltage_unit_mismatch_fail() {
+    let mut telegram = TelegramFields::default();
+    let result = handle_voltage("230*kW", &mut telegram, "voltage_p1");
+    assert!(matches!(result, Err(DsmrError::UnitMismatch(_))));
+    assert!(telegram.voltage_p1.is_empty());
+}
+#[test]
+fn test_handle_voltage_missing_unit_fail() {
+    let mut telegram = TelegramFields::default();
+    assert!(matches!(handle_voltage("230", &mut telegram, "voltage_p1"), Err(DsmrError::MissingUnit(_))));
+    assert!(matches!(handle_voltage("230*", &mut telegram, "voltage_p1"), Err(DsmrError::MissingUnit(_))));
+    assert!(matches!(handle_water("0000000012", &mut telegram), Err(DsmrError::MissingUnit(_))));
+    assert!(telegram.voltage_p1.is_empty());
+}
+#[test]
+fn test_parse_quantity_normalizes_smaller_units() {
+    assert_eq!(parse_quantity("1500*W", "kW").unwrap(), 1.5);
+    assert_eq!(parse_quantity("1.5*kW", "kW").unwrap(), 1.5);
+    // Only a smaller unit of the same quantity is accepted
+    assert!(matches!(parse_quantity("1500*Wh", "kW"), Err(DsmrError::UnitMismatch(_))));
+    assert!(matches!(parse_quantity("1500*W", "V"), Err(DsmrError::UnitMismatch(_))));
+    let mut telegram = TelegramFields::default();
+    handle_energy("0012345678.*Wh", &mut telegram, "energy_consumption").unwrap();
+    assert_eq!(telegram.energy_consumption, vec![12345.678]);
+    assert_eq!(telegram.energy_consumption_capacity, vec![10_000_000.0]);
+}
+#[test]
+fn test_handle_current_number_format_fail() {
+    let mut telegram = TelegramFields::default();
+    let result = handle_current("1O.5*A", &mut telegram, "current_p1");
+    assert!(matches!(result, Err(DsmrError::NumberFormat(_))));
+}
+#[test]
+fn test_handle_water_number_format_fail() {
+    let mut telegram = TelegramFields::default();
+    assert!(matches!(handle_water("-12*L", &mut telegram), Err(DsmrError::NumberFormat(_))));
+}
+#[test]
+fn test_parse_only_plain_decimals() {
+    for value in ["NaN", "inf", "-inf", "infinity", "1e3", "1E3", "1.5e-2", ".", "", "0x10", "1_000", " 1", "1.2.3"] {
+        let payload = format!("{value}*V");
+        assert!(matches!(parse_quantity(&payload, "V"), Err(DsmrError::NumberFormat(_))), "{payload}");
+    }
+    assert_eq!(parse_quantity("0012345678.*V", "V").unwrap(), 12345678.0);
+    assert_eq!(parse_quantity(".5*V", "V").unwrap(), 0.5);
+    // An exponent would otherwise make a register of 1 digit that holds 1000
+    let mut telegram = TelegramFields::default();
+    let result = handle_energy("1e3*kWh", &mut telegram, "energy_consumption");
+    assert!(matches!(result, Err(DsmrError::NumberFormat(_))));
+    assert!(telegram.energy_consumption_capacity.is_empty());
+}
+#[test]
+fn test_parse_sign_only_on_power() {
+    let mut telegram = TelegramFields::default();
+    handle_power("-0.5*kW", &mut telegram, "power_p1").unwrap();
+    handle_power("+1500*W", &mut telegram, "power_p2").unwrap();
+    assert_eq!(telegram.power_p1, vec![-0.5]);
+    assert_eq!(telegram.power_p2, vec![1.5]);
+    assert!(matches!(handle_power("--0.5*kW", &mut telegram, "power_p3"), Err(DsmrError::NumberFormat(_))));
+    assert!(matches!(handle_voltage("-230*V", &mut telegram, "voltage_p1"), Err(DsmrError::NumberFormat(_))));
+    assert!(matches!(handle_current("+1*A", &mut telegram, "current_p1"), Err(DsmrError::NumberFormat(_))));
+}
+#[test]
+fn test_parse_negative_counters_fail() {
+    let mut telegram = TelegramFields::default();
+    for (payload, key) in [("-1.0*kWh", "energy_consumption"), ("-1500*Wh", "energy_production"), ("+1.0*kWh", "energy_consumption")] {
+        assert!(matches!(handle_energy(payload, &mut telegram, key), Err(DsmrError::NumberFormat(_))), "{payload}");
+    }
+    assert!(matches!(handle_water("+12*L", &mut telegram), Err(DsmrError::NumberFormat(_))));
+    assert!(matches!(handle_gas_consumption("-00015.760*m3", &mut telegram), Err(DsmrError::NumberFormat(_))));
+    assert!(telegram.energy_consumption.is_empty() && telegram.water_consumption.is_empty() && telegram.gas_consumption.is_empty());
+}
+#[test]
+fn test_handle_power_unexpected_key_fail() {
+    let mut telegram = TelegramFields::default();
+    assert!(matches!(handle_power("1.0*kW", &mut telegram, "power_p4"), Err(DsmrError::KeyError(_))));
+}
+#[test]
+fn test_handle_gas_model_unknown_model_fail() {
+    let mut telegram = TelegramFields::default();
+    match handle_gas_model("G7", &mut telegram) {
+        Err(DsmrError::UnknownGasModel(message)) => assert!(message.contains("G7")),
+        other => panic!("Expected UnknownGasModel, got {:?}", other),
+    }
+}
+#[test]
+fn test_parse_missing_gas_model_fail() {
+    let input = "/v12\\+gr\n1.1.0#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n4.1#(W)\n6.1#(0000000012*L)\n\
+        1.1.1#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n4.1#(G)\n5.2#(00001.000*m3)\n1.2.1#(END)\n1.2.0#(END)\n";
+    assert!(matches!(parse(input).map_err(into_reason), Err(DsmrError::MissingGasModel(_))));
+}
+#[test]
+fn test_parse_invalid_date_fail() {
+    let input = "/v10\\\n1.1.0#(START)\n2.1#(23-Jul-05)\n4.1#(W)\n6.1#(0000000012*L)\n1.2.0#(END)\n";
+    assert!(matches!(parse(input).map_err(into_reason), Err(DsmrError::InvalidDate(_))));
+}
+#[test]
+fn test_parse_invalid_version_fail() {
+    let input = include_str!("../examples/bad/no_date.dsmr");
+    assert!(matches!(parse(input).map_err(into_reason), Err(DsmrError::VersionError(_))));
+}
+#[test]
+fn test_parse_error_span() {
+    let input = "/v10\\\n1.1.0#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n4.1#(E)\n1.2.0#(END)\n\n1.1.0#(START)\n7.1.1#(230*kW)\n1.2.0#(END)\n";
+    match parse(input) {
+        Err(DsmrError::Spanned(span, e)) => {
+            assert!(matches!(*e, DsmrError::UnitMismatch(_)));
+            assert_eq!(span, Span { offset: 84, line: 8, column: 8, telegram: 2, depth: 0 });
+            assert_eq!(&input[span.offset..span.offset + 5], "7.1.1");
+        }
+        other => panic!("Expected a spanned error, got {:?}", other),
+    }
+}
+#[test]
+fn test_parse_error_span_child_depth() {
+    let input = include_str!("../examples/good/simple_gas.dsmr").replace("5.1#(G4)", "5.1#(G4)\n9.9#(0)");
+    match parse(&input) {
+        Err(DsmrError::Spanned(span, e)) => {
+            assert!(matches!(*e, DsmrError::KeyError(_)));
+            assert_eq!((span.line, span.column, span.telegram, span.depth), (15, 1, 1, 1));
+        }
+        other => panic!("Expected a spanned error, got {:?}", other),
+    }
+}
+#[test]
+fn test_validation_error_points_at_telegram_start() {
+    let input = include_str!("../examples/good/two_packets.dsmr").replacen("4.1#(E)", "4.1#(E)\n4.1#(E)", 2);
+    match parse(&input) {
+        Err(DsmrError::Spanned(span, e)) => {
+            assert!(matches!(*e, DsmrError::InformationTypeCount(_)));
+            assert_eq!((span.line, span.telegram), (2, 1));
+        }
+        other => panic!("Expected a spanned error, got {:?}", other),
+    }
+}
+#[test]
+fn test_error_snippet() {
+    let input = "/v10\\\n1.1.0#(START)\n7.1.1#(230*kW)\n";
+    let error = DsmrError::UnitMismatch("Expected V".to_string()).at(&Span { offset: 20, line: 3, column: 8, telegram: 1, depth: 0 });
+    let expected = " --> line 3, column 8 (telegram 1, depth 0)\n  |\n3 | 7.1.1#(230*kW)\n  |        ^";
+    assert_eq!(error.snippet(input), Some(expected.to_string()));
+}
+#[test]
+fn test_parse_header_extension_order() {
+    let span = Span { line: 1, ..Default::default() };
+    let gr = parse_header("/v12\\+gr", &span).expect("valid header");
+    let rg = parse_header("/v12\\+rg\r", &span).expect("valid header");
+    assert_eq!(gr, rg);
+    assert_eq!(gr.version, Versions::V12);
+    assert_eq!(gr.extensions, BTreeSet::from([Extensions::Gas, Extensions::Recursive]));
+}
+#[test]
+fn test_parse_header_without_extensions() {
+    let span = Span { line: 1, ..Default::default() };
+    let header = parse_header("/v10\\", &span).expect("valid header");
+    assert_eq!(header, Header { version: Versions::V10, extensions: BTreeSet::new() });
+}
+#[test]
+fn test_parse_header_unknown_extension_fail() {
+    let span = Span { line: 1, ..Default::default() };
+    match parse_header("/v12\\+gx", &span) {
+        Err(DsmrError::Spanned(span, e)) => {
+            assert!(matches!(*e, DsmrError::ExtensionError(_)));
+            assert_eq!(span.column, 8);
+        }
+        other => panic!("Expected an extension error, got {:?}", other),
+    }
+}
+#[test]
+fn test_parse_header_malformed_fail() {
+    let span = Span { line: 1, ..Default::default() };
+    for header in ["/v1", "v12\\", "/v12\\gr", "/v12\\+", "/v12\\+gg", "/vé\\"] {
+        let result = parse_header(header, &span).map_err(into_reason);
+        assert!(
+            matches!(result, Err(DsmrError::HeaderError(_) | DsmrError::ExtensionError(_) | DsmrError::VersionError(_))),
+            "{header} should be rejected"
+        );
+    }
+}
+#[test]
+fn test_parse_short_input_fail() {
+    for input in ["", "/", "\n\n", "/v1"] {
+        assert!(matches!(parse(input).map_err(into_reason), Err(DsmrError::HeaderError(_))), "{input:?} should be rejected");
+    }
+}
+#[test]
+fn test_parse_crlf_input() {
+    let input = include_str!("../examples/good/simple_gas.dsmr").replace('\n', "\r\n");
+    let telegrams = parse(&input).expect("CRLF input should parse");
+    assert_eq!(telegrams[0].telegram_extensions, BTreeSet::from([Extensions::Gas, Extensions::Recursive]));
+}
+#[test]
+fn test_parse_rg_header() {
+    let input = include_str!("../examples/good_sequences/should_parse_3_recursive.dsmr");
+    let telegrams = parse(input).expect("rg header should parse");
+    assert_eq!(telegrams[0].telegram_extensions, BTreeSet::from([Extensions::Gas, Extensions::Recursive]));
+}
+#[test]
+fn test_parse_gas_in_version_10_fail() {
+    let input = include_str!("../examples/good/simple_gas.dsmr").replace("/v12\\+gr", "/v10\\");
+    assert!(matches!(parse(&input).map_err(into_reason), Err(DsmrError::FieldNotInVersion(_))));
+}
+#[test]
+fn test_parse_gas_without_extension_fail() {
+    let input = include_str!("../examples/good/simple_gas.dsmr").replace("/v12\\+gr", "/v12\\+r");
+    assert!(matches!(parse(&input).map_err(into_reason), Err(DsmrError::GasWithoutExtension(_))));
+}
+#[test]
+fn test_parse_child_without_extension_fail() {
+    let input = include_str!("../examples/good/simple_gas.dsmr").replace("/v12\\+gr", "/v12\\+g");
+    assert!(matches!(parse(&input).map_err(into_reason), Err(DsmrError::ChildWithoutExtension(_))));
+}
+#[test]
+fn test_parse_header_extension_in_version_10_fail() {
+    let span = Span { line: 1, ..Default::default() };
+    assert!(matches!(parse_header("/v10\\+g", &span).map_err(into_reason), Err(DsmrError::ExtensionError(_))));
+}
+#[test]
+fn test_check_header_allows_electricity_in_version_10() {
+    let header = Header { version: Versions::V10, extensions: BTreeSet::new() };
+    assert!(check_header_allows(&Keys::VoltageP1, "7.1.1", "230*V", &header).is_ok());
+    assert!(check_header_allows(&Keys::InformationType, "4.1", "E", &header).is_ok());
+    assert!(matches!(
+        check_header_allows(&Keys::InformationType, "4.1", "G", &header),
+        Err(DsmrError::FieldNotInVersion(_))
+    ));
+}
+#[test]
+fn test_reader_matches_parse() {
+    let inputs = [
+        include_str!("../examples/good/two_packets.dsmr"),
+        include_str!("../examples/good/reorder.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_0.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_5_recursive.dsmr"),
+    ];
+    for input in inputs {
+        let read: Result<Vec<Telegram>, DsmrError> = TelegramReader::new(input.as_bytes()).collect();
+        assert_eq!(read.unwrap(), parse(input).unwrap());
+    }
+}
+#[test]
+fn test_reader_yields_telegram_at_end() {
+    let input = include_str!("../examples/good/two_packets.dsmr");
+    let mut remaining = input.as_bytes();
+    let mut reader = TelegramReader::new(&mut remaining);
+    assert!(reader.next().unwrap().is_ok());
+    drop(reader);
+    // Nothing after the first 1.2.0#(END) has been read yet
+    let first_end = input.find("1.2.0#(END)").unwrap();
+    let next_line = input[first_end..].find('\n').unwrap() + first_end + 1;
+    assert_eq!(remaining, &input.as_bytes()[next_line..]);
+}
+#[test]
+fn test_reader_stops_after_error() {
+    let input = include_str!("../examples/good/two_packets.dsmr").replacen("4.1#(E)", "4.1#(X)", 1);
+    let mut reader = TelegramReader::new(input.as_bytes());
+    assert!(matches!(reader.next().map(|t| t.map_err(into_reason)), Some(Err(DsmrError::InformationType(_)))));
+    assert!(reader.next().is_none());
+}
+#[test]
+fn test_reader_unfinished_telegram_fail() {
+    let input = include_str!("../examples/good/simple_gas.dsmr").replace("1.2.0#(END)", "");
+    let mut reader = TelegramReader::new(input.as_bytes());
+    assert!(matches!(reader.next().map(|t| t.map_err(into_reason)), Some(Err(DsmrError::MissingField(_)))));
+    assert!(matches!(TelegramReader::new("".as_bytes()).next(), Some(Err(DsmrError::HeaderError(_)))));
+}
+#[test]
+fn test_reader_snippet() {
+    let input = include_str!("../examples/good/two_packets.dsmr");
+    let end = input.rfind("1.2.0#(END)").unwrap();
+    let input = format!("{}7.1.1#(230*kW)\n{}", &input[..end], &input[end..]);
+    let mut reader = TelegramReader::new(input.as_bytes());
+    let error = reader.find_map(Result::err).unwrap();
+    assert_eq!(reader.snippet(&error), error.snippet(&input));
+    assert!(reader.snippet(&error).unwrap().contains("7.1.1#(230*kW)"));
+}
+/// Hands out its chunks one read at a time, an empty chunk being a moment where nothing was appended yet
+struct Appended(Vec<Vec<u8>>);
+impl std::io::Read for Appended {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.0.is_empty() {
+            return Ok(0);
+        }
+        let chunk = self.0.remove(0);
+        buf[..chunk.len()].copy_from_slice(&chunk);
+        Ok(chunk.len())
+    }
+}
+#[test]
+fn test_follow_waits_for_appended_telegrams() {
+    let input = include_str!("../examples/good/two_packets.dsmr");
+    // Cut the input in the middle of a line, with nothing appended for a while in between
+    let (first, second) = input.split_at(input.find("1.2.0#(END)").unwrap() + 4);
+    let appended = Appended(vec![first.as_bytes().to_vec(), Vec::new(), Vec::new(), second.as_bytes().to_vec()]);
+    let follow = Follow::new(appended, std::time::Duration::ZERO);
+    let mut reader = TelegramReader::new(std::io::BufReader::new(follow));
+    assert_eq!(reader.next().unwrap().unwrap(), parse(input).unwrap()[0]);
+    assert_eq!(reader.next().unwrap().unwrap(), parse(input).unwrap()[1]);
+}
+#[test]
+fn test_parse_event_log() {
+    let input = include_str!("../examples/good/two_packets.dsmr");
+    let telegrams = parse(input).unwrap();
+    assert_eq!(telegrams[0].event_log, vec![
+        EventLogEntry { id: 1, severity: Severity::High, message: "Power Failure".to_string(), date: parse_datetime("23-Jul-02 13:12:00 (S)").unwrap() },
+        EventLogEntry { id: 2, severity: Severity::Low, message: "Voltage too low".to_string(), date: parse_datetime("23-Jul-05 13:37:00 (S)").unwrap() },
+    ]);
+    assert_eq!(collect_readings(&telegrams).event_log.len(), 4);
+}
+#[test]
+fn test_parse_event_log_incomplete_fail() {
+    let input = include_str!("../examples/good/two_packets.dsmr").replacen("3.3.2#(23-Jul-05 13:37:00 (S))\n", "", 1);
+    match parse(&input).map_err(into_reason) {
+        Err(DsmrError::MissingField(message)) => assert!(message.contains("3.3.2")),
+        other => panic!("expected a missing 3.3.2, got {other:?}"),
+    }
+}
+#[test]
+fn test_parse_event_log_duplicate_fail() {
+    let input = include_str!("../examples/good/two_packets.dsmr").replacen("3.1.2#(L)", "3.1.1#(L)", 1);
+    assert!(matches!(parse(&input).map_err(into_reason), Err(DsmrError::DuplicateField(_))));
+}
+#[test]
+fn test_event_log_key() {
+    assert_eq!(version_key("3.2.0").unwrap(), Keys::EventLogMessage(0));
+    assert_eq!(version_key("3.3.12").unwrap(), Keys::EventLogDate(12));
+    assert!(matches!(version_key("3.4.1"), Err(DsmrError::KeyError(_))));
+    assert!(matches!(version_key("3.1.x"), Err(DsmrError::KeyError(_))));
+}
+#[test]
+fn test_severity() {
+    assert_eq!(severity("H").unwrap(), Severity::High);
+    assert_eq!(severity("L").unwrap(), Severity::Low);
+    assert!(Severity::High > Severity::Low);
+    assert!(matches!(severity("X"), Err(DsmrError::UnknownSeverity(_))));
+}
+#[test]
+fn test_parse_unknown_severity_fail() {
+    let input = include_str!("../examples/good/two_packets.dsmr").replacen("3.1.2#(L)", "3.1.2#(M)", 1);
+    assert!(matches!(parse(&input).map_err(into_reason), Err(DsmrError::UnknownSeverity(_))));
+}
+#[test]
+fn test_events_at_least() {
+    let input = include_str!("../examples/good/two_packets.dsmr");
+    let readings = collect_readings(&parse(input).unwrap());
+    assert_eq!(readings.events_at_least(Severity::Low).count(), 4);
+    let high: Vec<&EventLogEntry> = readings.events_at_least(Severity::High).collect();
+    assert_eq!(high.len(), 2);
+    assert!(high.iter().all(|entry| entry.message == "Power Failure"));
+}
+#[test]
+fn test_hex_string_utf8() {
+    // Ends in a three byte character
+    assert_eq!(hex_string("53747230306d2e20e29aa1").unwrap(), "Str00m. ⚡");
+    assert_eq!(hex_string("").unwrap(), "");
+}
+#[test]
+fn test_hex_string_invalid_fail() {
+    assert!(matches!(hex_string("506g"), Err(DsmrError::InvalidHex(3, _))));
+    assert!(matches!(hex_string("506"), Err(DsmrError::InvalidHex(2, _))));
+    // e2 9a starts a three byte character that is cut off by 41
+    assert!(matches!(hex_string("5050e29a41"), Err(DsmrError::InvalidUtf8(4, _))));
+    assert!(matches!(hex_string("ff"), Err(DsmrError::InvalidUtf8(0, _))));
+}
+#[test]
+fn test_parse_invalid_hex_points_at_digit() {
+    let input = include_str!("../examples/good/two_packets.dsmr").replacen("3.2.1#(506f", "3.2.1#(50zf", 1);
+    match parse(&input) {
+        Err(DsmrError::Spanned(span, e)) => {
+            assert!(matches!(*e, DsmrError::InvalidHex(2, _)));
+            assert_eq!((span.line, span.column), (5, 10));
+        }
+        other => panic!("expected an invalid hex digit, got {other:?}"),
+    }
+}
+#[test]
+fn test_parse_datetime_calendar_fail() {
+    for date in ["23-Feb-29 12:00:00 (W)", "23-Apr-31 12:00:00 (S)", "23-Jan-00 12:00:00 (W)", "23-Jul-05 25:61:00 (S)", "23-Jul-05 12:00:60 (S)"] {
+        assert!(matches!(parse_datetime(date), Err(DsmrError::InvalidDate(_))), "{date} should be rejected");
+    }
+    assert_eq!(parse_datetime("24-Feb-29 12:00:00 (W)").unwrap().day(), 29);
+}
+#[test]
+fn test_parse_datetime_malformed_fail() {
+    for date in ["23-Jul-05 15:26 (S)", "23-Jul-05 15:26:41:00 (S)", "23-Jul-05 15:26:41 (X)", "23-Jul-05 15:26:41", "23-Jul-5 15:26:41 (S)", "23-Jul-05 +1:26:41 (S)", ""] {
+        assert!(matches!(parse_datetime(date), Err(DsmrError::InvalidDate(_))), "{date} should be rejected");
+    }
+}
+#[test]
+fn test_datetime_season_and_timestamp() {
+    let summer = parse_datetime("23-Jul-05 15:26:41 (S)").unwrap();
+    let winter = parse_datetime("23-Jul-05 15:26:41 (W)").unwrap();
+    assert_eq!((summer.season(), winter.season()), (Season::Summer, Season::Winter));
+    // Summer time is an hour further ahead of UTC
+    assert_eq!(winter.timestamp().unwrap() - summer.timestamp().unwrap(), 3600);
+    assert_eq!(summer.timestamp().unwrap(), 1688563601);
+    assert_eq!(summer.to_string(), "23-Jul-05 15:26:41 (S)");
+}
+#[test]
+fn test_parse_impossible_date_fail() {
+    let input = include_str!("../examples/good/simple_gas.dsmr").replacen("2.1#(23-Jul-05", "2.1#(23-Feb-30", 1);
+    assert!(matches!(parse(&input).map_err(into_reason), Err(DsmrError::InvalidDate(_))));
+}
+#[test]
+fn test_counters_examples_pass() {
+    let inputs = [
+        include_str!("../examples/good/two_packets.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_0.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_4.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_0_recursive.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_5_recursive.dsmr"),
+    ];
+    for input in inputs {
+        let mut counters = CounterValidator::new(OnRegression::Reject, 0.1);
+        for mut telegram in parse(input).unwrap() {
+            counters.check(&mut telegram).unwrap();
+        }
+        assert!(counters.resets().is_empty());
+    }
+}
+#[test]
+fn test_counters_same_time_stamp_are_separate_meters() {
+    // The first meter reads more than the second, and both go up by 10 kWh a minute later
+    let first = include_str!("../examples/good/two_packets.dsmr").replacen("7.4.1#(0011454892.*kWh)", "7.4.1#(0013454892.*kWh)", 1);
+    let later = first.replace("2.1#(23-Jul-05 15:26:41 (S))", "2.1#(23-Jul-05 15:27:41 (S))")
+        .replacen("7.4.1#(0013454892.*kWh)", "7.4.1#(0013454902.*kWh)", 1)
+        .replacen("7.4.1#(0012454892.*kWh)", "7.4.1#(0012454902.*kWh)", 1);
+    let input = format!("{first}\n\n{}", later.split_once('\n').unwrap().1);
+    let mut telegrams = parse(&input).unwrap();
+    let mut counters = CounterValidator::new(OnRegression::Reject, 0.1);
+    for telegram in telegrams.iter_mut() {
+        counters.check(telegram).unwrap();
+    }
+    assert!(counters.resets().is_empty());
+    // Swapping the meters of the later time stamp takes the consumed energy of the first and the produced energy of the second down
+    telegrams.swap(2, 3);
+    let mut counters = CounterValidator::new(OnRegression::Reset, 0.1);
+    for telegram in telegrams.iter_mut() {
+        counters.check(telegram).unwrap();
+    }
+    let meters: Vec<(&str, &str)> = counters.resets().iter().map(|reset| (reset.meter.as_str(), reset.field)).collect();
+    assert_eq!(meters, vec![("telegram", "7.4.1"), ("telegram 2 at its time stamp", "7.4.2")]);
+    assert_eq!(counters.resets()[0].to_string(), "7.4.1 of telegram was reset in telegram 3, it went from 13454892 to 12454902");
+    // Neither meter's energy is lost
+    let readings = collect_readings(&parse(&input).unwrap());
+    let t = telegrams[0].time_stamp;
+    assert_eq!(readings.counter_deltas("7.4.1", 0.1), vec![(t, 0.0), (t + 60, 20.0)]);
+}
+#[test]
+fn test_counters_regression_fail() {
+    // The third telegram reads 5 kWh consumed after 7 kWh in the second
+    let input = include_str!("../examples/good_sequences/should_parse_5_recursive.dsmr").replacen("7.4.1#(0000000016.*kWh)", "7.4.1#(0000000005.*kWh)", 1);
+    let mut telegrams = parse(&input).unwrap();
+    let mut counters = CounterValidator::new(OnRegression::Reject, 0.1);
+    counters.check(&mut telegrams[0]).unwrap();
+    counters.check(&mut telegrams[1]).unwrap();
+    match counters.check(&mut telegrams[2]) {
+        Err(DsmrError::CounterRegression(message)) => assert!(message.contains("7.4.1 of child 1") && message.contains("telegram 3")),
+        other => panic!("expected the consumed energy to go down, got {other:?}"),
+    }
+}
+#[test]
+fn test_counters_reset() {
+    let input = include_str!("../examples/good_sequences/should_parse_5_recursive.dsmr").replacen("6.1#(0000000024*L)", "6.1#(0000000004*L)", 1);
+    let mut telegrams = parse(&input).unwrap();
+    let mut counters = CounterValidator::new(OnRegression::Reset, 0.1);
+    for telegram in telegrams.iter_mut() {
+        counters.check(telegram).unwrap();
+    }
+    assert_eq!(counters.resets(), &[CounterReset { telegram: 3, meter: "telegram".to_string(), field: "6.1", from: 10.0, to: 4.0 }]);
+    // Counting goes on from the 10 litres read before the reset
+    let litres: Vec<u64> = telegrams.iter().take(4).map(|telegram| match telegram.information {
+        Information::Water { litres, .. } => litres,
+        _ => panic!("top-level telegrams are water meters"),
+    }).collect();
+    assert_eq!(litres, vec![0, 10, 14, 50]);
+    let readings = collect_readings(&telegrams);
+    assert!(vector_water(&readings, 0.1, Window::default()).iter().all(|data| data.water_delta < 1000));
+}
+#[test]
+fn test_vector_water_counter_down_no_panic() {
+    let water_data = vector_water(&meter_readings([(0, water(10)), (60, water(4))]), 0.1, Window::default());
+    assert_eq!(water_data[1].water_delta, 0);
+}
+#[test]
+fn test_register_capacity() {
+    assert_eq!(register_capacity("0000000010*L", 19).unwrap(), 10_000_000_000.0);
+    assert_eq!(register_capacity("0000000007.*kWh", 19).unwrap(), 10_000_000_000.0);
+    assert_eq!(register_capacity("00015.760*m3", 19).unwrap(), 100_000.0);
+    assert_eq!(register_capacity("+001.00*kW", 19).unwrap(), 1_000.0);
+    assert!(matches!(register_capacity("00015.760*m3", 4), Err(DsmrError::NumberFormat(_))));
+}
+#[test]
+fn test_register_wider_than_f64_fail() {
+    // 10 to the power 400 is infinite, and every decrease of such a register would look like a wrap
+    let mut telegram = TelegramFields::default();
+    let result = handle_energy(&format!("{}5.*kWh", "0".repeat(399)), &mut telegram, "energy_consumption");
+    assert!(matches!(result, Err(DsmrError::NumberFormat(_))));
+    handle_energy(&format!("{}5.*kWh", "0".repeat(307)), &mut telegram, "energy_consumption").unwrap();
+    assert!(telegram.energy_consumption_capacity[0].is_finite());
+    // A u64 holds the capacity of 19 digits, not of 20
+    handle_water(&format!("{}12*L", "0".repeat(17)), &mut telegram).unwrap();
+    assert!(matches!(handle_water(&format!("{}12*L", "0".repeat(18)), &mut telegram), Err(DsmrError::NumberFormat(_))));
+}
+#[test]
+fn test_rollover_delta() {
+    let rollover = Rollover::new(Some(100_000.0), 0.1);
+    // 99990 -> 5 is 15 counted across the wrap
+    assert!(rollover.wraps(99_990.0, 5.0));
+    assert_eq!(rollover.delta(99_990.0, 5.0), 15.0);
+    assert_eq!(rollover.delta(5.0, 20.0), 15.0);
+    // Wrapping from 50000 would mean 50005 was counted, more than 10% of the register: a reset
+    assert!(!rollover.wraps(50_000.0, 5.0));
+    assert_eq!(rollover.delta(50_000.0, 5.0), -49_995.0);
+    // Without a known capacity nothing wraps
+    assert!(!Rollover::default().wraps(99_990.0, 5.0));
+}
+#[test]
+fn test_vector_water_rollover() {
+    let readings = meter_readings([(0, water(9_999_999_990)), (60, water(9_999_999_998)), (120, water(6))]);
+    let water_data = vector_water(&readings, 0.1, Window::default());
+    assert_eq!(water_data.iter().map(|data| data.water_delta).collect::<Vec<u64>>(), vec![0, 8, 8]);
+}
+#[test]
+fn test_vector_gas_and_energy_rollover() {
+    let gas_data = vector_gas(&meter_readings([(0, gas(99_999.5)), (60, gas(0.5))]), 0.1, Window::default());
+    assert_eq!(gas_data[1].gas_delta, 1.0);
+    let readings = meter_readings([(0, electricity(9_999_999_999.0, 10.0)), (60, electricity(1.0, 12.0))]);
+    let energy_data = vector_energy(&readings, 0.1, Window::default());
+    assert_eq!((energy_data[1].produced, energy_data[1].consumed), (2.0, 2.0));
+}
+#[test]
+fn test_counters_rollover_is_not_a_regression() {
+    let input = include_str!("../examples/good/simple_gas.dsmr");
+    let first = input.replace("5.2#(12345.123*m3)", "5.2#(99999.000*m3)");
+    let second = input.replace("5.2#(12345.123*m3)", "5.2#(00002.000*m3)").replace("6.1#(0000000012*L)", "6.1#(0000000013*L)")
+        .replace("2.1#(23-Jul-05 15:26:41 (S))", "2.1#(23-Jul-05 15:27:41 (S))");
+    let mut counters = CounterValidator::new(OnRegression::Reject, 0.1);
+    counters.check(&mut parse(&first).unwrap().remove(0)).unwrap();
+    counters.check(&mut parse(&second).unwrap().remove(0)).unwrap();
+    // The same drop is a regression when wraps that large are not plausible
+    let mut counters = CounterValidator::new(OnRegression::Reject, 0.00001);
+    counters.check(&mut parse(&first).unwrap().remove(0)).unwrap();
+    assert!(matches!(counters.check(&mut parse(&second).unwrap().remove(0)), Err(DsmrError::CounterRegression(_))));
+}
+#[test]
+fn test_interval_start() {
+    assert_eq!(Interval::Reading.start(1709251170, 0), 1709251170);
+    assert_eq!(Interval::Minute.start(1709251170, 0), 1709251140);
+    assert_eq!(Interval::Hour.start(1709251170, 0), 1709247600);
+    assert_eq!(Interval::Day.start(1709251170, 0), 1709164800);
+    // 29 February 2024 is in the same month as the first of February, 1 March is not
+    assert_eq!(Interval::Month.start(1709251170, 0), 1706745600);
+    assert_eq!(Interval::Month.start(1709251210, 0), 1709251200);
+    assert_eq!(Interval::Month.start(-43200, 0), -2678400);
+    // 00:39:30 UTC on 1 March is 01:39:30 in Dutch winter time, an hour after local midnight
+    assert_eq!(Interval::Day.start(1709251170, 3600), 1709247600);
+    assert_eq!(Interval::Month.start(1709251170, 3600), 1709247600);
+    assert_eq!(Interval::Day.start(1709247599, 3600), 1709247600 - 86400);
+}
+#[test]
+fn test_sum_buckets() {
+    let values = [(0, 0.0), (30, 1.0), (59, 3.0), (60, 6.0), (3600, 10.0)];
+    let deltas = deltas(&values, Rollover::default());
+    assert_eq!(deltas, vec![(0, 0.0), (30, 1.0), (59, 2.0), (60, 3.0), (3600, 4.0)]);
+    let per_minute = Window { interval: Interval::Minute, buckets: None };
+    assert_eq!(sum_buckets(&deltas, per_minute, |_| 0), vec![(0, 3.0), (60, 3.0), (3600, 4.0)]);
+    let per_hour = Window { interval: Interval::Hour, buckets: None };
+    assert_eq!(sum_buckets(&deltas, per_hour, |_| 0), vec![(0, 6.0), (3600, 4.0)]);
+    // Only the most recent buckets are kept
+    let last_two = Window { interval: Interval::Reading, buckets: Some(2) };
+    assert_eq!(sum_buckets(&deltas, last_two, |_| 0), vec![(60, 3.0), (3600, 4.0)]);
+    assert!(sum_buckets(&[], Window::default(), |_| 0).is_empty());
+}
+#[test]
+fn test_buckets_follow_local_midnight() {
+    let telegram = |date: &str, litres: u64| format!("1.1.0#(START)\n2.1#({date})\n4.1#(W)\n6.1#({litres:010}*L)\n1.2.0#(END)\n");
+    let daily = |readings: &[(&str, u64)], interval: Interval| {
+        let input = format!("/v12\\\n{}", readings.iter().map(|(date, litres)| telegram(date, *litres)).collect::<String>());
+        let readings = collect_readings(&parse(&input).unwrap());
+        vector_water(&readings, 0.1, Window { interval, buckets: None }).into_iter()
+            .map(|data| (data.timestamp, data.water_delta))
+            .collect::<Vec<(i64, u64)>>()
+    };
+    let midnight = |date: &str| parse_datetime(date).unwrap().timestamp().unwrap();
+    // 23:30 and 00:30 in summer time are both on 5 July in UTC, but on two days on the clock of the meter
+    let readings = [("23-Jul-05 22:30:00 (S)", 0), ("23-Jul-05 23:30:00 (S)", 10), ("23-Jul-06 00:30:00 (S)", 30), ("23-Jul-06 01:30:00 (S)", 60)];
+    assert_eq!(daily(&readings, Interval::Day), vec![(midnight("23-Jul-05 00:00:00 (S)"), 10), (midnight("23-Jul-06 00:00:00 (S)"), 50)]);
+    // The same for the last night of a month
+    let readings = [("23-Jun-30 23:30:00 (S)", 0), ("23-Jul-01 00:30:00 (S)", 20)];
+    assert_eq!(daily(&readings, Interval::Month), vec![(midnight("23-Jun-01 00:00:00 (S)"), 0), (midnight("23-Jul-01 00:00:00 (S)"), 20)]);
+    // The day that summer time starts is a single bucket, stamped at the offset of its first reading
+    let readings = [("23-Mar-26 01:30:00 (W)", 0), ("23-Mar-26 03:30:00 (S)", 10), ("23-Mar-27 00:30:00 (S)", 30)];
+    assert_eq!(daily(&readings, Interval::Day), vec![(midnight("23-Mar-26 00:00:00 (W)"), 10), (midnight("23-Mar-27 00:00:00 (S)"), 20)]);
+}
+#[test]
+fn test_vector_energy_window() {
+    let readings = meter_readings((0..20).map(|i| (i64::from(i) * 600, electricity(f64::from(i), f64::from(2 * i)))));
+    // Every reading is kept now that there is no fixed buffer of 12
+    assert_eq!(vector_energy(&readings, 0.1, Window::default()).len(), 20);
+    let hourly = vector_energy(&readings, 0.1, Window { interval: Interval::Hour, buckets: None });
+    assert_eq!(hourly.iter().map(|data| (data.timestamp, data.produced, data.consumed)).collect::<Vec<(i64, f64, f64)>>(),
+        vec![(0, 5.0, 10.0), (3600, 6.0, 12.0), (7200, 6.0, 12.0), (10800, 2.0, 4.0)]);
+}
+#[test]
+fn test_join_gaps() {
+    let a = vec![(0, 1.0), (60, 2.0), (120, 3.0)];
+    let b = vec![(0, 10.0), (120, 30.0), (180, 40.0)];
+    assert_eq!(join([&a, &b], Gaps::Skip), vec![(0, [1.0, 10.0]), (120, [3.0, 30.0])]);
+    assert_eq!(join([&a, &b], Gaps::Hold), vec![(0, [1.0, 10.0]), (60, [2.0, 10.0]), (120, [3.0, 30.0]), (180, [3.0, 40.0])]);
+    // Nothing to hold before the first reading
+    assert_eq!(join([&a[1..], &b], Gaps::Hold), vec![(60, [2.0, 10.0]), (120, [3.0, 30.0]), (180, [3.0, 40.0])]);
+}
+#[test]
+fn test_readings_child_with_own_date() {
+    // The gas meter was read a minute after the water meter it hangs under
+    let input = include_str!("../examples/good/simple_gas.dsmr").replacen("1.1.1#(START)\n2.1#(23-Jul-05 15:26:41 (S))", "1.1.1#(START)\n2.1#(23-Jul-05 15:27:41 (S))", 1);
+    let telegrams = parse(&input).unwrap();
+    let readings = collect_readings(&telegrams);
+    let (water, gas) = (telegrams[0].time_stamp, telegrams[0].child_telegrams[0].time_stamp);
+    assert_eq!(gas - water, 60);
+    assert_eq!(readings.time_stamps(), vec![water, gas]);
+    assert_eq!(readings.counter("6.1"), vec![vec![(water, 12.0)]]);
+    assert_eq!(readings.counter("5.2"), vec![vec![(gas, 12345.123)]]);
+}
+#[test]
+fn test_counter_deltas_child_of_same_kind_with_own_date() {
+    // A meter with a meter of the same kind below it, read half a minute later and 4900 further along
+    let stream = |meter: &dyn Fn(u64) -> String| {
+        let telegram = |parent: &str, child: &str, reading: u64| format!(
+            "1.1.0#(START)\n2.1#(23-Jul-05 15:{parent} (S))\n{}\n1.1.1#(START)\n2.1#(23-Jul-05 15:{child} (S))\n{}\n1.2.1#(END)\n1.2.0#(END)\n",
+            meter(reading), meter(reading + 4900)
+        );
+        let input = format!("/v12\\+r\n{}{}{}", telegram("26:00", "26:30", 100), telegram("27:00", "27:30", 110), telegram("28:00", "28:30", 120));
+        let mut telegrams = parse(&input).unwrap();
+        let mut counters = CounterValidator::new(OnRegression::Reject, 0.1);
+        for telegram in telegrams.iter_mut() {
+            counters.check(telegram).unwrap();
+        }
+        telegrams
+    };
+    let telegrams = stream(&|litres| format!("4.1#(W)\n6.1#({litres:010}*L)"));
+    let readings = collect_readings(&telegrams);
+    let water = vector_water(&readings, 0.1, Window::default());
+    // Each meter is compared to its own previous reading, not to the other meter
+    assert_eq!(water.iter().map(|data| data.water_delta).collect::<Vec<u64>>(), vec![0, 0, 10, 10, 10, 10]);
+    let start = telegrams[0].time_stamp;
+    assert_eq!(water.iter().map(|data| data.timestamp - start).collect::<Vec<i64>>(), vec![0, 30, 60, 90, 120, 150]);
+    let per_minute = vector_water(&readings, 0.1, Window { interval: Interval::Minute, buckets: None });
+    assert_eq!(per_minute.iter().map(|data| data.water_delta).collect::<Vec<u64>>(), vec![0, 20, 20]);
+    // Energy is counted the same way
+    let telegrams = stream(&|kwh| format!(
+        "4.1#(E)\n7.1.1#(230.0*V)\n7.1.2#(230.0*V)\n7.1.3#(230.0*V)\n7.2.1#(1.0*A)\n7.2.2#(1.0*A)\n7.2.3#(1.0*A)\n\
+        7.3.1#(0.230*kW)\n7.3.2#(0.230*kW)\n7.3.3#(0.230*kW)\n7.4.1#({kwh:010}.0*kWh)\n7.4.2#({:010}.0*kWh)", kwh / 10
+    ));
+    let energy = vector_energy(&collect_readings(&telegrams), 0.1, Window::default());
+    assert_eq!(energy.iter().map(|data| (data.consumed, data.produced)).collect::<Vec<(f64, f64)>>(),
+        vec![(0.0, 0.0), (0.0, 0.0), (10.0, 1.0), (10.0, 1.0), (10.0, 1.0), (10.0, 1.0)]);
+}
+#[test]
+fn test_vector_water_missing_reading_keeps_time() {
+    // Every reading keeps the time of its own telegram, a phase that misses its first reading does not shift the others
+    let input = include_str!("../examples/good_sequences/should_parse_2_recursive.dsmr");
+    let telegrams = parse(input).unwrap();
+    let readings = collect_readings(&telegrams);
+    let water = vector_water(&readings, 0.1, Window::default());
+    let times: Vec<i64> = telegrams.iter().map(|telegram| telegram.time_stamp).collect();
+    assert_eq!(water.iter().map(|data| data.timestamp).collect::<Vec<i64>>(), times);
+    let [voltage_p1, voltage_p2, voltage_p3] = readings.voltage();
+    let voltage = vector_voltage(voltage_p1[1..].to_vec(), voltage_p2, voltage_p3, Gaps::Skip);
+    assert_eq!(voltage.first().map(|data| data.timestamp), times.get(1).copied());
+}
+#[test]
+fn test_serialize_round_trip_examples() {
+    let inputs = [
+        include_str!("../examples/good/reorder.dsmr"),
+        include_str!("../examples/good/simple_gas.dsmr"),
+        include_str!("../examples/good/two_packets.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_0.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_0_recursive.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_1.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_1_recursive.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_2.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_2_recursive.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_3.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_3_recursive.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_4.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_4_recursive.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_5_recursive.dsmr"),
+        include_str!("../examples/good_sequences/simple_electricity_seq.dsmr"),
+    ];
+    for input in inputs {
+        let telegrams = parse(input).unwrap();
+        let text = serialize(&telegrams);
+        assert_eq!(parse(&text).unwrap(), telegrams, "{text}");
+    }
+}
+#[test]
+fn test_serialize_lines() {
+    let input = include_str!("../examples/good/simple_gas.dsmr").replacen("5.1#(G4)", "5.1#(G5)", 1);
+    let text = serialize(&parse(&input).unwrap());
+    assert!(text.starts_with("/v12\\+gr\n1.1.0#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n3.1.1#(H)\n3.2.1#(506f776572204661696c757265)\n"));
+    // The gas reading is written as read, before it was converted by the G5 model
+    assert!(text.contains("1.1.1#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n4.1#(G)\n5.1#(G5)\n5.2#(12345.123*m3)\n1.2.1#(END)\n"));
+    assert!(text.contains("4.1#(W)\n6.1#(0000000012*L)\n1.1.1#(START)\n"));
+    assert_eq!(serialize(&[]), "");
+}
+#[test]
+fn test_serialize_register_widths() {
+    let input = "/v10\\\n1.1.0#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n4.1#(E)\n\
+        7.1.1#(230*V)\n7.1.2#(230*V)\n7.1.3#(230*V)\n7.2.1#(1*A)\n7.2.2#(1*A)\n7.2.3#(1*A)\n\
+        7.3.1#(-0.5*kW)\n7.3.2#(0*kW)\n7.3.3#(1500*W)\n7.4.1#(.5*kWh)\n7.4.2#(0001500.25*Wh)\n1.2.0#(END)\n";
+    let telegrams = parse(input).unwrap();
+    let text = serialize(&telegrams);
+    assert!(text.contains("7.3.1#(-0.5*kW)\n7.3.2#(0*kW)\n7.3.3#(1.5*kW)\n7.4.1#(.5*kWh)\n7.4.2#(0001.50025*kWh)\n"), "{text}");
+    assert_eq!(parse(&text).unwrap(), telegrams);
+}
+#[test]
+fn test_serialize_short_wh_registers() {
+    // Registers that wrap below 1 kWh stay in Wh, with their own width
+    for (register, written) in [("1*Wh", "1*Wh"), ("00*Wh", "00*Wh"), ("012.5*Wh", ".0125*kWh"), ("0999*Wh", "0.999*kWh")] {
+        let input = format!("/v10\\\n1.1.0#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n4.1#(E)\n\
+            7.1.1#(230*V)\n7.1.2#(230*V)\n7.1.3#(230*V)\n7.2.1#(1*A)\n7.2.2#(1*A)\n7.2.3#(1*A)\n\
+            7.3.1#(0*kW)\n7.3.2#(0*kW)\n7.3.3#(0*kW)\n7.4.1#({register})\n7.4.2#(0*kWh)\n1.2.0#(END)\n");
+        let telegrams = parse(&input).unwrap();
+        let text = serialize(&telegrams);
+        assert!(text.contains(&format!("7.4.1#({written})\n")), "{text}");
+        assert_eq!(parse(&text).unwrap(), telegrams);
+    }
+}
+#[test]
+fn test_serialize_widest_registers() {
+    // The widest registers whose capacity still fits round-trip, one digit more is rejected when parsing
+    let input = include_str!("../examples/good/simple_gas.dsmr");
+    let water = input.replace("6.1#(0000000012*L)", &format!("6.1#({}12*L)", "0".repeat(17)));
+    let telegrams = parse(&water).unwrap();
+    assert_eq!(parse(&serialize(&telegrams)).unwrap(), telegrams);
+    let water = input.replace("6.1#(0000000012*L)", &format!("6.1#({}12*L)", "0".repeat(18)));
+    assert!(matches!(parse(&water).map_err(into_reason), Err(DsmrError::NumberFormat(_))));
+    let gas = input.replace("5.2#(12345.123*m3)", &format!("5.2#({}5.123*m3)", "0".repeat(307)));
+    let telegrams = parse(&gas).unwrap();
+    assert_eq!(parse(&serialize(&telegrams)).unwrap(), telegrams);
+}
+#[test]
+fn test_generator_parses_back() {
+    let options = GeneratorOptions {
+        telegrams: 3 * 24 * 4,
+        interval: 15 * 60,
+        top_level: vec![Meter::Electricity, Meter::Water],
+        children: vec![Meter::Gas, Meter::Electricity, Meter::Water],
+        depth: 2,
+        event_rate: 0.2,
+        gas_model: GasModel::G6,
+        seed: 42,
+        ..Default::default()
+    };
+    let generator = Generator::new(options.clone()).unwrap();
+    assert_eq!(header_line(&generator.header()), "/v12\\+gr");
+    let telegrams: Vec<Telegram> = generator.collect::<Result<_, _>>().unwrap();
+    assert_eq!(telegrams.len(), 288);
+    // Three days later, in the same season
+    assert_eq!(telegrams[287].telegram_date, DsmrDateTime::new(2023, 7, 7, 23, 45, 0, Season::Summer).unwrap());
+    let mut parsed = parse(&serialize(&telegrams)).unwrap();
+    assert_eq!(parsed, telegrams);
+    let mut counters = CounterValidator::new(OnRegression::Reject, 0.1);
+    for telegram in parsed.iter_mut() {
+        counters.check(telegram).unwrap();
+    }
+    // The same seed gives the same stream, another seed does not
+    let again: Vec<Telegram> = Generator::new(options.clone()).unwrap().collect::<Result<_, _>>().unwrap();
+    assert_eq!(again, telegrams);
+    let other: Vec<Telegram> = Generator::new(GeneratorOptions { seed: 43, ..options }).unwrap().collect::<Result<_, _>>().unwrap();
+    assert_ne!(other, telegrams);
+}
+#[test]
+fn test_generator_without_children() {
+    let options = GeneratorOptions { telegrams: 10, children: vec![], top_level: vec![Meter::Water], ..Default::default() };
+    let generator = Generator::new(options).unwrap();
+    assert_eq!(header_line(&generator.header()), "/v12\\");
+    let telegrams: Vec<Telegram> = generator.collect::<Result<_, _>>().unwrap();
+    assert_eq!(parse(&serialize(&telegrams)).unwrap(), telegrams);
+}
+#[test]
+fn test_generator_rejects_invalid_options() {
+    let gas_on_top = GeneratorOptions { top_level: vec![Meter::Gas], ..Default::default() };
+    assert!(matches!(Generator::new(gas_on_top), Err(DsmrError::InformationType(_))));
+    let four_children = GeneratorOptions { children: vec![Meter::Gas; 4], ..Default::default() };
+    assert!(matches!(Generator::new(four_children), Err(DsmrError::TooManyChildren(_))));
+    // Every telegram would have the same date
+    let no_interval = GeneratorOptions { interval: 0, ..Default::default() };
+    assert!(matches!(Generator::new(no_interval), Err(DsmrError::InvalidInterval(_))));
+    // Telegrams only write two digit years
+    let start = DsmrDateTime::new(2099, 12, 31, 23, 59, 0, Season::Winter).unwrap();
+    let past_2099 = GeneratorOptions { telegrams: 2, start, ..Default::default() };
+    let results: Vec<Result<Telegram, DsmrError>> = Generator::new(past_2099).unwrap().collect();
+    assert!(results[0].is_ok());
+    assert!(matches!(results[1], Err(DsmrError::InvalidDate(_))));
+}
+#[test]
+fn test_fault_corpus() {
+    let inputs = [
+        include_str!("../examples/good/reorder.dsmr"),
+        include_str!("../examples/good/simple_gas.dsmr"),
+        include_str!("../examples/good/two_packets.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_1.dsmr"),
+        include_str!("../examples/good_sequences/should_parse_2_recursive.dsmr"),
+        include_str!("../examples/good_sequences/simple_electricity_seq.dsmr"),
+    ];
+    for input in inputs {
+        for case in fault_corpus(input) {
+            let label = format!("{} ({}), expected {}", case.fault, case.description, case.expected);
+            match parse(&case.input) {
+                Err(e) => assert_eq!(e.kind(), case.expected, "{label}: {e}"),
+                Ok(_) => panic!("{label}: was accepted"),
+            }
+            // Reading one telegram at a time has to reject it for the same reason
+            let error = TelegramReader::new(case.input.as_bytes()).find_map(Result::err);
+            assert_eq!(error.map(|e| e.kind()), Some(case.expected), "{label}");
+        }
+    }
+}
+#[test]
+fn test_fault_corpus_covers_every_fault() {
+    let corpus = fault_corpus(include_str!("../examples/good/simple_gas.dsmr"));
+    for fault in Fault::ALL {
+        assert!(corpus.iter().any(|case| case.fault == fault), "no {fault} case");
+    }
+    // A gas meter at the top is rejected
+    let gas = corpus.iter().find(|case| case.fault == Fault::GasAtTopLevel).unwrap();
+    assert!(gas.input.contains("1.1.0#(START)\n2.1#(23-Jul-05 15:26:41 (S))\n3.1.1#(H)"));
+    assert!(matches!(parse(&gas.input).map_err(into_reason), Err(DsmrError::TopLevelGas(_))));
+    assert!(fault_corpus("no telegrams here").is_empty());
+}
+
+// Near-valid inputs for the property tests below
+const VALID_INPUTS: [&str; 6] = [
+    include_str!("../examples/good/reorder.dsmr"),
+    include_str!("../examples/good/simple_gas.dsmr"),
+    include_str!("../examples/good/two_packets.dsmr"),
+    include_str!("../examples/good_sequences/should_parse_1.dsmr"),
+    include_str!("../examples/good_sequences/should_parse_2_recursive.dsmr"),
+    include_str!("../examples/good_sequences/simple_electricity_seq.dsmr"),
+];
+
+/// Feeds `bytes` through everything that runs on untrusted input, like `main` does.
+/// Any outcome is fine as long as nothing panics.
+fn check_no_panic(bytes: &[u8]) {
+    let input = String::from_utf8_lossy(bytes);
+    match parse(&input) {
+        Ok(telegrams) => graph_data(&collect_readings(&telegrams)),
+        Err(e) => {
+            let _ = (e.to_string(), e.kind(), e.snippet(&input));
+        }
+    }
+    let mut reader = TelegramReader::new(bytes);
+    let mut counters = CounterValidator::new(OnRegression::Reset, 0.1);
+    let mut readings = Readings::default();
+    while let Some(telegram) = reader.next() {
+        match telegram.and_then(|mut telegram| counters.check(&mut telegram).map(|_| telegram)) {
+            Ok(telegram) => readings.add(&telegram),
+            Err(e) => {
+                let _ = reader.snippet(&e);
+                break;
+            }
+        }
+    }
+    graph_data(&readings);
+}
+
+fn graph_data(readings: &Readings) {
+    let windows = [Window::default(), Window { interval: Interval::Hour, buckets: Some(2) }, Window { interval: Interval::Month, buckets: None }];
+    for (window, gaps) in windows.into_iter().zip([Gaps::Skip, Gaps::Hold, Gaps::Skip]) {
+        let [voltage_p1, voltage_p2, voltage_p3] = readings.voltage();
+        vector_voltage(voltage_p1, voltage_p2, voltage_p3, gaps);
+        let [current_p1, current_p2, current_p3] = readings.current();
+        vector_current(current_p1, current_p2, current_p3, gaps);
+        vector_energy(readings, 0.1, window);
+        vector_water(readings, 0.1, window);
+        vector_gas(readings, 0.1, window);
+    }
+}
+
+/// A small change to a valid input, at a place picked relative to its length
+#[derive(Debug, Clone)]
+enum Mutation {
+    DropLine(prop::sample::Index),
+    DuplicateLine(prop::sample::Index),
+    SwapLines(prop::sample::Index, prop::sample::Index),
+    InsertByte(prop::sample::Index, u8),
+    DeleteByte(prop::sample::Index),
+    ReplaceByte(prop::sample::Index, u8),
+    ReplaceValue(prop::sample::Index, String),
+}
+
+fn value() -> impl Strategy<Value = String> {
+    prop_oneof![
+        // Values close to what a field can hold
+        prop::sample::select(vec![
+            "", "*", "()", "-1*L", "-0.5*kWh", "NaN*V", "inf*kW", "1e400*kWh", "99999999999999999999*L",
+            "18446744073709551616*L", "0.000*m3", "G7", "E", "W", "G", "X", "H", "L", "4", "abc", "é",
+            "00-Jan-01 00:00:00 (S)", "99-Dec-31 23:59:59 (W)", "23-Feb-30 25:61:61 (S)", "23-Jul-05 15:26:41",
+            "23-Mar-26 02:30:00 (S)", "23-Oct-29 02:30:00 (W)", "c3", "c328", "f09f", "ffff",
+        ]).prop_map(String::from),
+        "[0-9a-fA-F]{0,12}",
+        "[-+0-9.eE]{0,16}\\*(kWh|kW|Wh|W|V|A|L|m3|)",
+        "[ -~]{0,20}",
+        any::<String>(),
+    ]
+}
+
+fn mutation() -> impl Strategy<Value = Mutation> {
+    prop_oneof![
+        any::<prop::sample::Index>().prop_map(Mutation::DropLine),
+        any::<prop::sample::Index>().prop_map(Mutation::DuplicateLine),
+        (any::<prop::sample::Index>(), any::<prop::sample::Index>()).prop_map(|(a, b)| Mutation::SwapLines(a, b)),
+        (any::<prop::sample::Index>(), any::<u8>()).prop_map(|(index, byte)| Mutation::InsertByte(index, byte)),
+        any::<prop::sample::Index>().prop_map(Mutation::DeleteByte),
+        (any::<prop::sample::Index>(), any::<u8>()).prop_map(|(index, byte)| Mutation::ReplaceByte(index, byte)),
+        (any::<prop::sample::Index>(), value()).prop_map(|(index, value)| Mutation::ReplaceValue(index, value)),
+    ]
+}
+
+fn mutate(input: &[u8], mutations: &[Mutation]) -> Vec<u8> {
+    let mut lines: Vec<Vec<u8>> = input.split(|byte| *byte == b'\n').map(<[u8]>::to_vec).collect();
+    for mutation in mutations {
+        match mutation {
+            Mutation::DropLine(index) => {
+                lines.remove(index.index(lines.len()));
+            }
+            Mutation::DuplicateLine(index) => {
+                let line = lines[index.index(lines.len())].clone();
+                lines.insert(index.index(lines.len()), line);
+            }
+            Mutation::SwapLines(a, b) => {
+                let (a, b) = (a.index(lines.len()), b.index(lines.len()));
+                lines.swap(a, b);
+            }
+            Mutation::InsertByte(index, byte) => {
+                let line = index.get_mut(&mut lines);
+                line.insert(index.index(line.len() + 1), *byte);
+            }
+            Mutation::DeleteByte(index) | Mutation::ReplaceByte(index, _) => {
+                let line = index.get_mut(&mut lines);
+                if line.is_empty() {
+                    continue;
+                }
+                let at = index.index(line.len());
+                match mutation {
+                    Mutation::ReplaceByte(_, byte) => line[at] = *byte,
+                    _ => {
+                        line.remove(at);
+                    }
+                }
+            }
+            Mutation::ReplaceValue(index, value) => {
+                let line = index.get_mut(&mut lines);
+                if let Some(start) = line.iter().position(|byte| *byte == b'(') {
+                    let end = line.iter().rposition(|byte| *byte == b')').filter(|end| *end > start).unwrap_or(line.len());
+                    line.splice(start + 1..end, value.bytes());
+                }
+            }
+        }
+        if lines.is_empty() {
+            lines.push(Vec::new());
+        }
+    }
+    lines.join(&b'\n')
+}
+
+/// A valid stream from the generator, for inputs with more meters and nesting than the examples have
+fn generated_input() -> impl Strategy<Value = String> {
+    let meter = prop::sample::select(vec![Meter::Electricity, Meter::Water, Meter::Gas]);
+    let top_level = prop::sample::select(vec![vec![Meter::Electricity], vec![Meter::Water], vec![Meter::Water, Meter::Electricity]]);
+    (1..4usize, top_level, prop::collection::vec(meter, 0..=3), 0..3usize, 0.0..0.5, any::<u64>()).prop_map(
+        |(telegrams, top_level, children, depth, event_rate, seed)| {
+            let options = GeneratorOptions { telegrams, top_level, children, depth, event_rate, seed, ..Default::default() };
+            let generator = Generator::new(options).unwrap();
+            let header = header_line(&generator.header());
+            let telegrams: Vec<Telegram> = generator.map(Result::unwrap).collect();
+            format!("{header}\n{}", telegrams.iter().map(serialize_telegram).collect::<String>())
+        },
+    )
+}
+
+#[test]
+fn test_no_panic_on_crashers() {
+    // Inputs that crashed the parser once, and anything the fuzz target finds, see fuzz/fuzz_targets/parse.rs
+    let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/crashers");
+    let mut count = 0;
+    for entry in std::fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        let bytes = std::fs::read(&path).unwrap();
+        let result = std::panic::catch_unwind(|| check_no_panic(&bytes));
+        assert!(result.is_ok(), "{} panics", path.display());
+        count += 1;
+    }
+    assert!(count > 0);
+    // The ones that used to slice or index past the end of a header or date
+    for input in [include_str!("../examples/crashers/short_header.dsmr"), include_str!("../examples/crashers/multibyte_header.dsmr")] {
+        assert!(matches!(parse(input).map_err(into_reason), Err(DsmrError::HeaderError(_))));
+    }
+    assert!(matches!(parse(include_str!("../examples/crashers/short_date.dsmr")).map_err(into_reason), Err(DsmrError::InvalidDate(_))));
+    // A 20 digit register is fine, the negative one next to it is not
+    let result = parse(include_str!("../examples/crashers/huge_counters.dsmr")).map_err(into_reason);
+    assert!(matches!(result, Err(DsmrError::NumberFormat(ref e)) if e.contains("-1.0*kWh")), "{result:?}");
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn test_no_panic_on_arbitrary_bytes(bytes in prop::collection::vec(any::<u8>(), 0..512)) {
+        check_no_panic(&bytes);
+    }
+
+    #[test]
+    fn test_no_panic_on_arbitrary_lines(lines in prop::collection::vec("[0-9.]{0,6}#\\([ -~]{0,24}\\)|[ -~]{0,30}", 0..40)) {
+        check_no_panic(format!("/v12\\+gr\n1.1.0#(START)\n{}", lines.join("\n")).as_bytes());
+    }
+
+    #[test]
+    fn test_no_panic_on_mutated_examples(input in prop::sample::select(VALID_INPUTS.to_vec()), mutations in prop::collection::vec(mutation(), 1..6)) {
+        check_no_panic(&mutate(input.as_bytes(), &mutations));
+    }
+
+    #[test]
+    fn test_no_panic_on_mutated_generated_streams(input in generated_input(), mutations in prop::collection::vec(mutation(), 1..6)) {
+        check_no_panic(&mutate(input.as_bytes(), &mutations));
+    }
+
+    #[test]
+    fn test_generated_streams_parse(input in generated_input()) {
+        prop_assert!(parse(&input).is_ok());
+    }
+}